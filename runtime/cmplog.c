@@ -0,0 +1,207 @@
+// Comparison logging runtime for anomie's --cmplog option.
+//
+// Build a second copy of the target with comparison tracing and link this
+// file in. The runtime itself must be built without tracing, eg.
+//
+//   cc -O2 -c cmplog.c -o cmplog.o
+//   cc -fsanitize-coverage=trace-cmp -fno-builtin -o target-cmplog target.c cmplog.o
+//
+// then pass it with `--cmplog target-cmplog`. Every comparison whose operands
+// differ is written to the file named by ANOMIE_CMPLOG. The file is mapped, so
+// the log survives the target crashing. memcmp and the str*cmp functions are
+// replaced to log their operands too, -fno-builtin keeps the compiler from
+// inlining them.
+//
+// Binaries built with AFL++'s cmplog instrumentation can't be used instead,
+// their comparisons go to AFL's shared memory map, which anomie doesn't read.
+#include <fcntl.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+#define CMPLOG_MAGIC 0x504d4341 // "ACMP"
+#define MAX_ENTRIES 8192
+#define MAX_OPERAND 32
+#define SEEN_BITS (1 << 16)
+
+enum { KIND_INT = 0, KIND_BYTES = 1 };
+
+// layout read back by src/cmplog.rs, integers are little endian
+struct entry {
+  uint8_t kind;
+  uint8_t a_len;
+  uint8_t b_len;
+  uint8_t reserved[5];
+  uint8_t a[MAX_OPERAND];
+  uint8_t b[MAX_OPERAND];
+};
+
+struct log {
+  uint32_t magic;
+  uint32_t count;
+  struct entry entries[MAX_ENTRIES];
+};
+
+static struct log *cmplog;
+static int opened;
+// comparisons in loops repeat a lot, only the first of each is logged
+static uint8_t seen[SEEN_BITS / 8];
+
+static struct log *open_log(void) {
+  if (opened) {
+    return cmplog;
+  }
+  opened = 1;
+
+  const char *path = getenv("ANOMIE_CMPLOG");
+  if (!path) {
+    return NULL;
+  }
+  int fd = open(path, O_RDWR | O_CREAT | O_TRUNC, 0600);
+  if (fd < 0) {
+    return NULL;
+  }
+  if (ftruncate(fd, sizeof(struct log)) == 0) {
+    void *map = mmap(NULL, sizeof(struct log), PROT_READ | PROT_WRITE,
+                     MAP_SHARED, fd, 0);
+    if (map != MAP_FAILED) {
+      cmplog = map;
+      cmplog->magic = CMPLOG_MAGIC;
+    }
+  }
+  close(fd);
+  return cmplog;
+}
+
+static uint32_t fnv1a(uint32_t hash, const uint8_t *data, size_t len) {
+  for (size_t i = 0; i < len; i++) {
+    hash = (hash ^ data[i]) * 16777619u;
+  }
+  return hash;
+}
+
+static void log_operands(uint8_t kind, const uint8_t *a, size_t a_len,
+                         const uint8_t *b, size_t b_len) {
+  a_len = a_len < MAX_OPERAND ? a_len : MAX_OPERAND;
+  b_len = b_len < MAX_OPERAND ? b_len : MAX_OPERAND;
+  struct log *log = open_log();
+  if (log && log->count < MAX_ENTRIES) {
+    uint32_t hash = fnv1a(2166136261u, &kind, 1);
+    hash = fnv1a(hash, a, a_len);
+    hash = fnv1a(hash ^ 0xFF, b, b_len) % SEEN_BITS;
+    if (!(seen[hash / 8] & (1 << (hash % 8)))) {
+      seen[hash / 8] |= 1 << (hash % 8);
+
+      struct entry *entry = &log->entries[log->count++];
+      entry->kind = kind;
+      entry->a_len = a_len;
+      entry->b_len = b_len;
+      for (size_t i = 0; i < a_len; i++) {
+        entry->a[i] = a[i];
+      }
+      for (size_t i = 0; i < b_len; i++) {
+        entry->b[i] = b[i];
+      }
+    }
+  }
+}
+
+static void log_int(uint64_t a, uint64_t b, size_t width) {
+  if (a == b) {
+    return;
+  }
+  uint8_t a_bytes[8], b_bytes[8];
+  for (size_t i = 0; i < 8; i++) {
+    a_bytes[i] = a >> (8 * i);
+    b_bytes[i] = b >> (8 * i);
+  }
+  log_operands(KIND_INT, a_bytes, width, b_bytes, width);
+}
+
+void __sanitizer_cov_trace_cmp1(uint8_t a, uint8_t b) { log_int(a, b, 1); }
+void __sanitizer_cov_trace_cmp2(uint16_t a, uint16_t b) { log_int(a, b, 2); }
+void __sanitizer_cov_trace_cmp4(uint32_t a, uint32_t b) { log_int(a, b, 4); }
+void __sanitizer_cov_trace_cmp8(uint64_t a, uint64_t b) { log_int(a, b, 8); }
+void __sanitizer_cov_trace_const_cmp1(uint8_t a, uint8_t b) { log_int(a, b, 1); }
+void __sanitizer_cov_trace_const_cmp2(uint16_t a, uint16_t b) { log_int(a, b, 2); }
+void __sanitizer_cov_trace_const_cmp4(uint32_t a, uint32_t b) { log_int(a, b, 4); }
+void __sanitizer_cov_trace_const_cmp8(uint64_t a, uint64_t b) { log_int(a, b, 8); }
+
+// gcc traces floating point comparisons too, their bits are logged as is
+void __sanitizer_cov_trace_cmpf(float a, float b) {
+  union { float f; uint32_t u; } x = {a}, y = {b};
+  log_int(x.u, y.u, 4);
+}
+
+void __sanitizer_cov_trace_cmpd(double a, double b) {
+  union { double f; uint64_t u; } x = {a}, y = {b};
+  log_int(x.u, y.u, 8);
+}
+
+// cases[0] is the number of cases and cases[1] the width of the value in bits
+void __sanitizer_cov_trace_switch(uint64_t value, uint64_t *cases) {
+  for (uint64_t i = 0; i < cases[0]; i++) {
+    log_int(value, cases[i + 2], cases[1] / 8);
+  }
+}
+
+static int lower(int c) { return c >= 'A' && c <= 'Z' ? c + 32 : c; }
+
+int memcmp(const void *s1, const void *s2, size_t n) {
+  const uint8_t *a = s1, *b = s2;
+  size_t i = 0;
+  while (i < n && a[i] == b[i]) {
+    i++;
+  }
+  if (i == n) {
+    return 0;
+  }
+  log_operands(KIND_BYTES, a, n, b, n);
+  return a[i] - b[i];
+}
+
+int bcmp(const void *s1, const void *s2, size_t n) { return memcmp(s1, s2, n); }
+
+static int compare_strings(const char *s1, const char *s2, size_t n,
+                           int ignore_case) {
+  const uint8_t *a = (const uint8_t *)s1, *b = (const uint8_t *)s2;
+  size_t i = 0;
+  while (i < n && a[i] && (ignore_case ? lower(a[i]) == lower(b[i]) : a[i] == b[i])) {
+    i++;
+  }
+  if (i == n) {
+    return 0;
+  }
+  int diff = ignore_case ? lower(a[i]) - lower(b[i]) : a[i] - b[i];
+  if (diff == 0) {
+    return 0;
+  }
+
+  size_t a_len = 0, b_len = 0;
+  while (a_len < n && a_len < MAX_OPERAND && a[a_len]) {
+    a_len++;
+  }
+  while (b_len < n && b_len < MAX_OPERAND && b[b_len]) {
+    b_len++;
+  }
+  log_operands(KIND_BYTES, a, a_len, b, b_len);
+  return diff;
+}
+
+int strcmp(const char *s1, const char *s2) {
+  return compare_strings(s1, s2, SIZE_MAX, 0);
+}
+
+int strncmp(const char *s1, const char *s2, size_t n) {
+  return compare_strings(s1, s2, n, 0);
+}
+
+int strcasecmp(const char *s1, const char *s2) {
+  return compare_strings(s1, s2, SIZE_MAX, 1);
+}
+
+int strncasecmp(const char *s1, const char *s2, size_t n) {
+  return compare_strings(s1, s2, n, 1);
+}
@@ -0,0 +1,57 @@
+#!/usr/bin/env python3
+# Example external mutator for anomie, see src/external.rs for the protocol.
+#
+#   anomie -b ./server --fuzz-type external \
+#       --external-mutator "python3 runtime/external_mutator.py" -- "{input}"
+#
+# The messages it works on are a type byte followed by a length prefixed
+# payload. Mutations keep the length consistent, so they get past the
+# target's framing checks.
+import json
+import random
+import sys
+
+
+def seeds():
+    return [
+        {"name": "hello", "data": bytes([1, 5]).hex() + b"hello".hex()},
+        {"name": "empty", "data": bytes([2, 0]).hex()},
+    ]
+
+
+def mutate(data, rng):
+    if len(data) < 2:
+        return None, None
+    kind, payload = data[0], bytearray(data[2:])
+    choice = rng.randrange(3)
+    if choice == 0:
+        kind = rng.randrange(256)
+        description = f"set the type to {kind}"
+    elif choice == 1 and payload:
+        i = rng.randrange(len(payload))
+        payload[i] = rng.randrange(256)
+        description = f"changed payload byte {i}"
+    else:
+        extra = bytes(rng.randrange(256) for _ in range(rng.randrange(1, 16)))
+        payload += extra
+        description = f"appended {len(extra)} bytes"
+    payload = payload[:255]
+    return bytes([kind, len(payload)]) + payload, description
+
+
+for line in sys.stdin:
+    request = json.loads(line)
+    if request["op"] == "seeds":
+        reply = {"seeds": seeds()}
+    elif request["op"] == "mutate":
+        # seeded by anomie, the same request has to give the same result
+        rng = random.Random(request["rng_seed"])
+        mutated, description = mutate(bytes.fromhex(request["data"]), rng)
+        if mutated is None:
+            reply = {"data": None}
+        else:
+            mutated = mutated[: request["max_len"]]
+            reply = {"data": mutated.hex(), "description": description}
+    else:
+        reply = {"error": f"unknown op {request['op']}"}
+    print(json.dumps(reply), flush=True)
@@ -1,15 +1,23 @@
 use anyhow::Result;
 use log::{debug, info};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fs::{self, remove_file},
     io::Read,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime},
 };
 
 use crate::{
     errors::{self, ExitStatus},
+    mutate::{self, MutationRecord},
+    mutations::schedule::MutatorStats,
+    oracle::{self, OracleAction, OracleRule},
+    target::TargetOutput,
+    triage::{self, RUST_PANIC_EXIT_CODE, RustPanic, Severity, StackFrame},
     types::{CrashStats, StructuredInput},
+    utils,
 };
 
 #[derive(Serialize)]
@@ -17,25 +25,151 @@ pub struct CrashAnalyzer {
     pub crashes: Vec<Crash>,
     pub report_path: PathBuf,
     pub stats: CrashStats,
+    pub started_at: String,
+    pub duration_ms: u128,
+    pub mutators: Vec<MutatorStats>, // how effective each mutator was
+
+    #[serde(skip)]
+    oracles: Vec<OracleRule>,
+    #[serde(skip)]
+    start: Instant,
 }
 
-#[derive(Serialize)]
+// only keep the start of stderr, sanitizer reports put the useful bits up top
+const STDERR_EXCERPT_LEN: usize = 4096;
+
+#[derive(Serialize, Deserialize)]
 pub struct Crash {
     pub file: String,
-    pub mutations: Vec<String>,
+    pub category: String,
+    pub severity: Severity,
+    pub fault_address: Option<u64>,
+    #[serde(default)]
+    pub iteration: usize,
+    #[serde(default)]
+    pub elapsed_ms: u128,
+    #[serde(default)]
+    pub found_at: String,
+    #[serde(default)]
+    pub exit_status: Option<ExitStatus>,
+    #[serde(default)]
+    pub duration_ms: u128, // how long the target ran for
+    #[serde(default)]
+    pub peak_memory_kb: Option<u64>,
+    #[serde(default)]
+    pub size: usize,
+    #[serde(default)]
+    pub crc32: String,
+    #[serde(default)]
+    pub stderr: String,
+    #[serde(default)]
+    pub frames: Vec<StackFrame>,
+    #[serde(default)]
+    pub panic: Option<RustPanic>,
+    #[serde(default)]
+    pub faulting_instruction: Option<String>,
+    #[serde(default)]
+    pub debugger_output: Option<String>,
+    #[serde(default)]
+    pub seed: Option<Seed>,
+    #[serde(default)]
+    pub derivation: Option<String>, // of inputs in the grammar fuzz type's grammar
+    #[serde(deserialize_with = "mutate::deserialize_records")]
+    pub mutations: Vec<MutationRecord>,
+}
+
+// The corpus entry an input was mutated from. Seeds that led to a crash are
+// copied into the report's seeds folder since the corpus itself is temporary.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Seed {
+    pub name: String,
+    pub crc32: String,
+    pub file: String,
+}
+
+impl Seed {
+    pub fn new(name: &str, content: &[u8], report_path: &str, extension: &str) -> Self {
+        let crc32 = format!("{:08x}", crc32fast::hash(content));
+        let extension = if extension.is_empty() {
+            "bin"
+        } else {
+            extension
+        };
+        Self {
+            name: name.to_string(),
+            file: format!("{report_path}/seeds/seed-{crc32}.{extension}"),
+            crc32,
+        }
+    }
+
+    // seeds are named by their checksum, so a seed is only written once
+    pub fn save(&self, content: &[u8]) -> Result<()> {
+        if !Path::new(&self.file).exists() {
+            debug!("Saving seed {} to {}", self.name, self.file);
+            fs::write(&self.file, content)?;
+        }
+        Ok(())
+    }
+}
+
+impl Crash {
+    // crashes with the same signature are considered the same bug
+    pub fn signature(&self) -> String {
+        triage::signature(
+            &self.category,
+            self.severity,
+            &self.frames,
+            self.panic.as_ref(),
+        )
+    }
+
+    // writes the crash record next to its input as `crash-N.json`, so a crash
+    // file can be moved elsewhere without losing its context
+    pub fn write_sidecar(&self) -> Result<()> {
+        let sidecar_path = Path::new(&self.file).with_extension("json");
+        fs::write(&sidecar_path, serde_json::to_string_pretty(self)?)?;
+        debug!("Wrote crash metadata to {}", sidecar_path.display());
+        Ok(())
+    }
+}
+
+// returns the report folder a result belongs in, or None if the run passed
+pub fn crash_category(output: &TargetOutput, oracles: &[OracleRule]) -> Option<String> {
+    if triage::is_out_of_memory(output) {
+        return Some("OOM".into());
+    }
+
+    match &output.status {
+        // an expected exit code can't hide a panic or a sanitizer report, ASan
+        // exits with 1 by default. a finding rule still names them
+        ExitStatus::ExitCode(code) => match oracle::evaluate(oracles, *code, &output.stderr) {
+            Some(rule) if rule.action == OracleAction::Finding => Some(rule.name.clone()),
+            _ if is_rust_panic(*code, &output.stderr) => Some("PANIC".into()),
+            _ if triage::has_sanitizer_report(&output.stderr) => Some("SANITIZER".into()),
+            _ => None,
+        },
+        ExitStatus::Signal(sig) => Some(errors::describe_signal(*sig).1),
+        ExitStatus::Timeout(_) => Some("TIMEOUT".into()),
+        ExitStatus::Error(_) => Some("UNKNOWN".into()),
+    }
+}
+
+// an unwinding rust panic exits with 101, make sure it wasn't a regular exit(101)
+fn is_rust_panic(code: i32, stderr: &str) -> bool {
+    code == RUST_PANIC_EXIT_CODE && stderr.contains("panicked at")
 }
 
 impl CrashAnalyzer {
-    pub fn new(report_path: PathBuf) -> Self {
+    pub fn new(report_path: PathBuf, oracles: Vec<OracleRule>) -> Self {
         let stats = CrashStats {
             total: 0,
-            sigill: 0,
-            sigabrt: 0,
-            sigfpe: 0,
-            sigsegv: 0,
-            sigpipe: 0,
-            sigterm: 0,
+            signals: BTreeMap::new(),
             timeout: 0,
+            oom: 0,
+            unknown: 0,
+            sanitizer: 0,
+            panic: 0,
+            findings: BTreeMap::new(),
         };
 
         let crashes: Vec<Crash> = Vec::new();
@@ -43,20 +177,84 @@ impl CrashAnalyzer {
             crashes,
             report_path,
             stats,
+            started_at: utils::iso8601(SystemTime::now()),
+            duration_ms: 0,
+            mutators: Vec::new(),
+            oracles,
+            start: Instant::now(),
         }
     }
 
+    // marks the end of the run, records how long it took
+    pub fn finish(&mut self) {
+        self.duration_ms = self.start.elapsed().as_millis();
+    }
+
     pub fn analyze(
         &mut self,
         crash_id: usize,
-        result: ExitStatus,
+        output: TargetOutput,
         input: StructuredInput,
-        mutation_array: Vec<String>,
+        mutation_array: Vec<MutationRecord>,
     ) -> Result<()> {
-        let mut name: &str = "";
+        let mut name = String::new();
         let mut crash_occurred = false;
 
-        match result {
+        let oracle = match &output.status {
+            ExitStatus::ExitCode(code) => oracle::evaluate(&self.oracles, *code, &output.stderr),
+            _ => None,
+        };
+
+        match &output.status {
+            _ if triage::is_out_of_memory(&output) => {
+                self.stats.oom += 1;
+                info!(
+                    "Hit! Process ran out of memory (peak {} KB). Recording in {}/OOM/ as crash-{crash_id}",
+                    output.peak_rss_kb.unwrap_or_default(),
+                    self.report_path.display()
+                );
+                name = "OOM".into();
+                crash_occurred = true;
+            }
+            ExitStatus::ExitCode(code)
+                if oracle.is_some_and(|rule| rule.action == OracleAction::Finding) =>
+            {
+                name = oracle.map_or_else(String::new, |rule| rule.name.clone());
+                *self.stats.findings.entry(name.clone()).or_default() += 1;
+                info!(
+                    "Hit! Process exited with code {code} matching oracle rule {name}. Recording in {}/{name}/ as crash-{crash_id}",
+                    self.report_path.display()
+                );
+                crash_occurred = true;
+            }
+            ExitStatus::ExitCode(code) if is_rust_panic(*code, &output.stderr) => {
+                self.stats.panic += 1;
+                info!(
+                    "Hit! Process panicked. Recording in {}/PANIC/ as crash-{crash_id}",
+                    self.report_path.display()
+                );
+                name = "PANIC".into();
+                crash_occurred = true;
+            }
+            ExitStatus::ExitCode(code) if triage::has_sanitizer_report(&output.stderr) => {
+                self.stats.sanitizer += 1;
+                info!(
+                    "Hit! Process exited with code {code} after a sanitizer report. Recording in {}/SANITIZER/ as crash-{crash_id}",
+                    self.report_path.display()
+                );
+                name = "SANITIZER".into();
+                crash_occurred = true;
+            }
+            // checked after the panic and sanitizer arms so the rule can't hide them
+            ExitStatus::ExitCode(code) if oracle.is_some() => {
+                debug!(
+                    "Process exited with code {code}, expected by oracle rule {}",
+                    oracle.map_or("", |rule| rule.name.as_str())
+                );
+                if let StructuredInput::FileInput { path, .. } = &input {
+                    remove_file(path)?;
+                }
+            }
             ExitStatus::ExitCode(code) => {
                 debug!("Process exited gracefully with code {code}");
                 if let StructuredInput::FileInput { path, .. } = &input {
@@ -65,33 +263,8 @@ impl CrashAnalyzer {
             }
             ExitStatus::Signal(sig) => {
                 let desc: &str;
-                (desc, name) = match sig {
-                    errors::SIGILL => {
-                        self.stats.sigill += 1;
-                        ("illegal instruction", "SIGILL")
-                    }
-                    errors::SIGABRT => {
-                        self.stats.sigabrt += 1;
-                        ("abort function", "SIGABRT")
-                    }
-                    errors::SIGFPE => {
-                        self.stats.sigfpe += 1;
-                        ("floating point exception", "SIGFPE")
-                    }
-                    errors::SIGSEGV => {
-                        self.stats.sigsegv += 1;
-                        ("segmentation fault", "SIGSEGV")
-                    }
-                    errors::SIGPIPE => {
-                        self.stats.sigpipe += 1;
-                        ("pipe error", "SIGPIPE")
-                    }
-                    errors::SIGTERM => {
-                        self.stats.sigterm += 1;
-                        ("termination error", "SIGTERM")
-                    }
-                    _ => ("unknown error", "UNKNOWN"),
-                };
+                (desc, name) = errors::describe_signal(*sig);
+                *self.stats.signals.entry(name.clone()).or_default() += 1;
 
                 info!(
                     "Hit! Process crashed due to a {desc} error ({name}). Recording in {}/{name}/ as crash-{crash_id}",
@@ -102,18 +275,21 @@ impl CrashAnalyzer {
             ExitStatus::Timeout(limit) => {
                 self.stats.timeout += 1;
                 info!("Hit! Process timed out after exceeding {limit} ms");
-                name = "TIMEOUT";
+                name = "TIMEOUT".into();
                 crash_occurred = true;
             }
             ExitStatus::Error(msg) => {
+                self.stats.unknown += 1;
                 info!("Hit! Process execution error: {msg}");
-                name = "UNKNOWN";
+                name = "UNKNOWN".into();
                 crash_occurred = true;
             }
         }
 
         if crash_occurred {
-            self.record_crash(crash_id, input, name, mutation_array)?;
+            let triage = triage::classify(&output);
+            debug!("Triaged crash-{crash_id} as {}", triage.severity.label());
+            self.record_crash(crash_id, input, &name, triage, &output, mutation_array)?;
             self.stats.total += 1;
         }
 
@@ -125,7 +301,9 @@ impl CrashAnalyzer {
         crash_id: usize,
         input: StructuredInput,
         crash_type: &str,
-        mutation_array: Vec<String>,
+        triage: triage::Triage,
+        output: &TargetOutput,
+        mutation_array: Vec<MutationRecord>,
     ) -> Result<()> {
         let (output_path, bytes) = match input {
             StructuredInput::StringInput(bytes) => {
@@ -146,15 +324,53 @@ impl CrashAnalyzer {
             }
         };
 
+        // report folders are created the first time something lands in them
+        fs::create_dir_all(self.report_path.join(crash_type))?;
         debug!("Recording crash at {output_path}");
-        fs::write(&output_path, bytes)?;
+        fs::write(&output_path, &bytes)?;
 
         let crash = Crash {
             file: output_path,
+            category: crash_type.to_string(),
+            severity: triage.severity,
+            fault_address: triage.fault_address,
+            iteration: crash_id,
+            elapsed_ms: self.start.elapsed().as_millis(),
+            found_at: utils::iso8601(SystemTime::now()),
+            exit_status: Some(output.status.clone()),
+            duration_ms: output.duration_ms,
+            peak_memory_kb: output.peak_rss_kb,
+            size: bytes.len(),
+            crc32: format!("{:08x}", crc32fast::hash(&bytes)),
+            stderr: stderr_excerpt(&output.stderr),
+            frames: triage.frames,
+            panic: triage.panic,
+            faulting_instruction: None,
+            debugger_output: None,
+            seed: None,
+            derivation: None,
             mutations: mutation_array,
         };
         self.crashes.push(crash);
 
         Ok(())
     }
+
+    // order the recorded crashes from most to least severe, crashes in the
+    // same bucket keep the order they were found in
+    pub fn sort_by_severity(&mut self) {
+        self.crashes.sort_by_key(|crash| crash.severity);
+    }
+}
+
+fn stderr_excerpt(stderr: &str) -> String {
+    if stderr.len() <= STDERR_EXCERPT_LEN {
+        return stderr.to_string();
+    }
+
+    let mut end = STDERR_EXCERPT_LEN;
+    while !stderr.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n[truncated]", &stderr[..end])
 }
@@ -0,0 +1,227 @@
+use std::{collections::HashSet, fs};
+
+use anyhow::{Result, anyhow};
+
+use crate::dictionary::Dictionary;
+
+// where the tokens extracted from the target are saved in the report, it can
+// be passed back with --dict
+pub const AUTO_DICTIONARY_FILE: &str = "auto.dict";
+
+// printable runs outside these lengths are unlikely to be compared against
+// the input as a whole
+const MIN_TOKEN_LEN: usize = 4;
+const MAX_TOKEN_LEN: usize = 32;
+// shorter runs are kept when they are a whole C string, eg. "GIF"
+const MIN_MAGIC_LEN: usize = 2;
+// whole C strings with non-printable bytes up to this long are kept as binary
+// magic values, eg. "\x89PNG\r\n\x1a\n"
+const MAX_BINARY_MAGIC_LEN: usize = 8;
+// the shortest tokens are kept when a binary has more than this
+const MAX_AUTO_TOKENS: usize = 512;
+
+const SHT_NOBITS: u32 = 8;
+
+// Builds a dictionary from the printable strings and short magic values, both
+// text and binary, in the read-only data of an ELF binary. Magic values the
+// compiler turns into immediates (eg. a 4 byte chunk type compared as an int)
+// aren't in the read-only data and can't be found this way.
+pub fn extract(bin_path: &str) -> Result<Dictionary> {
+    let bin = fs::read(bin_path).map_err(|e| anyhow!("can't read {bin_path}: {e}"))?;
+    let elf = Elf::parse(&bin)?;
+
+    // large binaries have tens of thousands of strings, duplicates are
+    // found with a set
+    let mut seen: HashSet<&[u8]> = HashSet::new();
+    let mut tokens: Vec<&[u8]> = Vec::new();
+    for section in elf.rodata_sections()? {
+        for token in printable_runs(section) {
+            if is_interesting(token) && seen.insert(token) {
+                tokens.push(token);
+            }
+        }
+        for token in binary_magic(section) {
+            if seen.insert(token) {
+                tokens.push(token);
+            }
+        }
+    }
+
+    // magic values and keywords tend to be short, long runs are mostly messages
+    tokens.sort_by_key(|token| token.len());
+    tokens.truncate(MAX_AUTO_TOKENS);
+    Ok(Dictionary::from_tokens(&tokens))
+}
+
+// runs of printable ascii, short ones only when they are NUL delimited
+fn printable_runs(data: &[u8]) -> Vec<&[u8]> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for (i, &byte) in data.iter().chain(&[0]).enumerate() {
+        if (0x20..=0x7E).contains(&byte) {
+            continue;
+        }
+
+        let run = &data[start..i];
+        let nul_delimited = byte == 0 && (start == 0 || data[start - 1] == 0);
+        if (MIN_TOKEN_LEN..=MAX_TOKEN_LEN).contains(&run.len())
+            || (nul_delimited && (MIN_MAGIC_LEN..MIN_TOKEN_LEN).contains(&run.len()))
+        {
+            runs.push(run);
+        }
+        start = i + 1;
+    }
+    runs
+}
+
+// short NUL terminated strings with non-printable bytes. tables of binary data
+// are full of those, so only ones with two letters in a row (the "PNG" of
+// "\x89PNG", "PK" of "PK\x03\x04") are taken as magic values
+fn binary_magic(data: &[u8]) -> Vec<&[u8]> {
+    let mut strings: Vec<&[u8]> = data.split(|&b| b == 0).collect();
+    // the last one isn't NUL terminated
+    strings.pop();
+    strings
+        .into_iter()
+        .filter(|s| (MIN_MAGIC_LEN..=MAX_BINARY_MAGIC_LEN).contains(&s.len()))
+        .filter(|s| s.iter().any(|b| !(0x20..=0x7E).contains(b)) && !s.contains(&b'%'))
+        .filter(|s| {
+            s.windows(2)
+                .any(|pair| pair.iter().all(u8::is_ascii_alphabetic))
+        })
+        .collect()
+}
+
+// drops format strings, sentences, source paths and filler
+fn is_interesting(token: &[u8]) -> bool {
+    let spaces = token.iter().filter(|&&b| b == b' ').count();
+    let text = String::from_utf8_lossy(token);
+
+    token.iter().any(u8::is_ascii_alphanumeric)
+        && !token.contains(&b'%')
+        && spaces <= 1
+        && token.first() != Some(&b' ')
+        && token.last() != Some(&b' ')
+        && !text.contains(".rs")
+        && !text.starts_with('/')
+        && token.iter().any(|&b| b != token[0])
+}
+
+// Just enough of an ELF parser to find the section contents
+struct Elf<'a> {
+    bin: &'a [u8],
+    is_64: bool,
+    big_endian: bool,
+}
+
+struct SectionHeader {
+    name: u32,
+    kind: u32,
+    offset: u64,
+    size: u64,
+}
+
+impl<'a> Elf<'a> {
+    fn parse(bin: &'a [u8]) -> Result<Self> {
+        if bin.len() < 6 || bin[..4] != *b"\x7FELF" {
+            return Err(anyhow!("not an ELF binary"));
+        }
+        let is_64 = match bin[4] {
+            1 => false,
+            2 => true,
+            class => return Err(anyhow!("unknown ELF class {class}")),
+        };
+        let big_endian = match bin[5] {
+            1 => false,
+            2 => true,
+            encoding => return Err(anyhow!("unknown ELF data encoding {encoding}")),
+        };
+        Ok(Self {
+            bin,
+            is_64,
+            big_endian,
+        })
+    }
+
+    fn read(&self, offset: u64, width: usize) -> Result<u64> {
+        let field = usize::try_from(offset)
+            .ok()
+            .and_then(|offset| self.bin.get(offset..offset.checked_add(width)?))
+            .ok_or_else(|| anyhow!("ELF field at {offset:#x} is out of bounds"))?;
+
+        let mut buf = [0u8; 8];
+        buf[..width].copy_from_slice(field);
+        if self.big_endian {
+            buf[..width].reverse();
+        }
+        Ok(u64::from_le_bytes(buf))
+    }
+
+    // address sized fields are 4 or 8 bytes depending on the class
+    fn read_addr(&self, offset: u64) -> Result<u64> {
+        self.read(offset, if self.is_64 { 8 } else { 4 })
+    }
+
+    fn section_headers(&self) -> Result<Vec<SectionHeader>> {
+        let (shoff, entsize, shnum) = if self.is_64 {
+            (0x28, 0x3A, 0x3C)
+        } else {
+            (0x20, 0x2E, 0x30)
+        };
+        let shoff = self.read_addr(shoff)?;
+        // keeps the header offsets below from overflowing
+        if shoff > self.bin.len() as u64 {
+            return Err(anyhow!("ELF section headers are out of bounds"));
+        }
+        let entsize = self.read(entsize, 2)?;
+        let shnum = self.read(shnum, 2)?;
+
+        // sh_offset and sh_size follow sh_flags and sh_addr, which are address sized
+        let (offset_field, size_field) = if self.is_64 {
+            (0x18, 0x20)
+        } else {
+            (0x10, 0x14)
+        };
+        (0..shnum)
+            .map(|i| {
+                let header = shoff + i * entsize;
+                Ok(SectionHeader {
+                    name: u32::try_from(self.read(header, 4)?)?,
+                    kind: u32::try_from(self.read(header + 4, 4)?)?,
+                    offset: self.read_addr(header + offset_field)?,
+                    size: self.read_addr(header + size_field)?,
+                })
+            })
+            .collect()
+    }
+
+    fn contents(&self, section: &SectionHeader) -> Result<&'a [u8]> {
+        usize::try_from(section.offset)
+            .ok()
+            .zip(usize::try_from(section.size).ok())
+            .and_then(|(offset, size)| self.bin.get(offset..offset.checked_add(size)?))
+            .ok_or_else(|| anyhow!("ELF section at {:#x} is out of bounds", section.offset))
+    }
+
+    // .rodata and the .rodata.* sections split out by the compiler
+    fn rodata_sections(&self) -> Result<Vec<&'a [u8]>> {
+        let headers = self.section_headers()?;
+        let shstrndx = self.read(if self.is_64 { 0x3E } else { 0x32 }, 2)?;
+        let names = headers
+            .get(usize::try_from(shstrndx)?)
+            .ok_or_else(|| anyhow!("ELF section name table is missing"))?;
+        let names = self.contents(names)?;
+
+        let mut sections = Vec::new();
+        for header in &headers {
+            let name = names
+                .get(header.name as usize..)
+                .and_then(|name| name.split(|&b| b == 0).next())
+                .unwrap_or_default();
+            if header.kind != SHT_NOBITS && (name == b".rodata" || name.starts_with(b".rodata.")) {
+                sections.push(self.contents(header)?);
+            }
+        }
+        Ok(sections)
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    collections::HashSet,
+    fs,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{Result, anyhow};
+use log::{debug, warn};
+
+use crate::{
+    mutations::bytes::read_int,
+    target::{spawn_target, target_command},
+    types::Config,
+};
+
+// the runtime (runtime/cmplog.c) logs comparisons to the file named by this
+// variable
+pub const CMPLOG_ENV: &str = "ANOMIE_CMPLOG";
+
+// "ACMP", written by the runtime once it has mapped the log
+const MAGIC: u32 = 0x504D_4341;
+const HEADER_LEN: usize = 8;
+const OPERAND_LEN: usize = 32;
+const ENTRY_LEN: usize = 8 + 2 * OPERAND_LEN;
+
+// a missing log is only warned about once
+static WARNED_NO_LOG: AtomicBool = AtomicBool::new(false);
+
+const KIND_INT: u8 = 0;
+const KIND_BYTES: u8 = 1;
+
+// The operands of a comparison the target made that didn't hold
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Comparison {
+    Int { width: usize, a: u64, b: u64 }, // width in bytes
+    Bytes { a: Vec<u8>, b: Vec<u8> },     // memcmp and the str*cmp functions
+}
+
+// Runs the comparison tracing build of the target with the given args and
+// returns the comparisons it made. The target crashing is fine, whatever it
+// compared before that is still logged.
+pub fn trace(config: &Config, cmplog_path: &str, args: Vec<String>) -> Result<Vec<Comparison>> {
+    let log_path = config.temp_dir.path().join("cmplog.log");
+    let mut command = target_command(cmplog_path);
+    command.args(args).env(CMPLOG_ENV, &log_path);
+
+    let output = spawn_target(config, command)?;
+    debug!("Comparison tracing run finished with {:?}", output.status);
+
+    // the runtime creates the log on the first comparison, a binary that
+    // never writes one most likely wasn't linked with it (eg. an afl build)
+    let Ok(log) = fs::read(&log_path) else {
+        if !WARNED_NO_LOG.swap(true, Ordering::Relaxed) {
+            warn!(
+                "{cmplog_path} didn't write a comparison log, is it linked with runtime/cmplog.c? AFL++ cmplog builds aren't supported"
+            );
+        }
+        return Ok(Vec::new());
+    };
+    fs::remove_file(&log_path)?;
+    parse(&log)
+}
+
+pub fn parse(log: &[u8]) -> Result<Vec<Comparison>> {
+    let field = |offset: usize| {
+        log.get(offset..offset + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap_or_default()))
+    };
+    if field(0) != Some(MAGIC) {
+        return Err(anyhow!("comparison log has an invalid header"));
+    }
+    let count = field(4).unwrap_or_default() as usize;
+
+    let mut comparisons = Vec::new();
+    let mut seen = HashSet::new();
+    for entry in log[HEADER_LEN..].chunks_exact(ENTRY_LEN).take(count) {
+        let (a_len, b_len) = (usize::from(entry[1]), usize::from(entry[2]));
+        if a_len > OPERAND_LEN || b_len > OPERAND_LEN {
+            return Err(anyhow!("comparison log has an invalid entry"));
+        }
+        let a = &entry[8..8 + a_len];
+        let b = &entry[8 + OPERAND_LEN..8 + OPERAND_LEN + b_len];
+
+        let comparison = match entry[0] {
+            KIND_INT if a_len == b_len && matches!(a_len, 1 | 2 | 4 | 8) => Comparison::Int {
+                width: a_len,
+                a: read_int(a, false),
+                b: read_int(b, false),
+            },
+            KIND_BYTES => Comparison::Bytes {
+                a: a.to_vec(),
+                b: b.to_vec(),
+            },
+            _ => return Err(anyhow!("comparison log has an invalid entry")),
+        };
+        if seen.insert(comparison.clone()) {
+            comparisons.push(comparison);
+        }
+    }
+    Ok(comparisons)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(kind: u8, a: &[u8], b: &[u8]) -> Vec<u8> {
+        let mut entry = vec![0; ENTRY_LEN];
+        entry[0] = kind;
+        entry[1] = a.len() as u8;
+        entry[2] = b.len() as u8;
+        entry[8..8 + a.len()].copy_from_slice(a);
+        entry[8 + OPERAND_LEN..8 + OPERAND_LEN + b.len()].copy_from_slice(b);
+        entry
+    }
+
+    fn log(entries: &[Vec<u8>], count: u32) -> Vec<u8> {
+        let mut log = MAGIC.to_le_bytes().to_vec();
+        log.extend(count.to_le_bytes());
+        log.extend(entries.concat());
+        log
+    }
+
+    #[test]
+    fn parses_a_comparison_log() {
+        let int = entry(KIND_INT, &0x1234u16.to_le_bytes(), &0xBEEFu16.to_le_bytes());
+        let bytes = entry(KIND_BYTES, b"GIF8", b"\x89PNG");
+        // the duplicate is dropped, entries past the count are ignored
+        let ignored = entry(KIND_BYTES, b"a", b"b");
+        let comparisons = parse(&log(&[int.clone(), bytes, int, ignored], 3)).unwrap();
+        assert_eq!(
+            comparisons,
+            vec![
+                Comparison::Int {
+                    width: 2,
+                    a: 0x1234,
+                    b: 0xBEEF
+                },
+                Comparison::Bytes {
+                    a: b"GIF8".to_vec(),
+                    b: b"\x89PNG".to_vec()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_logs() {
+        assert!(parse(b"nope").is_err());
+        let mut invalid = entry(KIND_BYTES, b"", b"");
+        invalid[1] = OPERAND_LEN as u8 + 1;
+        assert!(parse(&log(&[invalid], 1)).is_err());
+        assert!(parse(&log(&[entry(KIND_INT, &[1, 2, 3], &[1, 2, 3])], 1)).is_err());
+    }
+}
@@ -1,12 +1,79 @@
 use anyhow::{Result, anyhow};
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use log::warn;
 use rand::{SeedableRng, rngs::SmallRng};
-use std::fs;
+use std::{fs, path::Path, rc::Rc};
 use tempfile::tempdir;
 
-use crate::types::{Config, FuzzType};
+use crate::{
+    autodict,
+    dictionary::Dictionary,
+    external::ExternalMutator,
+    grammar::Grammar,
+    mutations::{MutatorPattern, MutatorSelection},
+    oracle,
+    types::{Config, FuzzType},
+};
 
 #[derive(Parser, Debug)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    // running without a subcommand starts a fuzzing campaign
+    #[command(flatten)]
+    pub fuzz: Option<RawConfig>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Replay the crashes of previous runs against the current binary
+    Regress(Box<RegressArgs>),
+    /// Compare the crashes, stats and configuration of two runs
+    Diff(DiffArgs),
+    /// Regenerate crash inputs by re-applying their recorded mutations to their seeds
+    Replay(ReplayArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// Report directory (eg. `reports/0001`) whose crashes are regenerated
+    pub report: String,
+
+    /// Only replay the crash found at this iteration
+    #[arg(long)]
+    pub iteration: Option<usize>,
+}
+
+#[derive(Args, Debug)]
+pub struct DiffArgs {
+    /// Report directory of the baseline run
+    pub old: String,
+
+    /// Report directory of the run being compared against the baseline
+    pub new: String,
+
+    /// Where the JSON summary is written
+    #[arg(long, default_value = "diff.json")]
+    pub json: String,
+}
+
+#[derive(Args, Debug)]
+pub struct RegressArgs {
+    /// Report directories (eg. `reports/0001`) whose crashes are replayed
+    #[arg(required = true)]
+    pub reports: Vec<String>,
+
+    /// Where the JSON summary is written
+    #[arg(long, default_value = "regress.json")]
+    pub json: String,
+
+    #[command(flatten)]
+    pub target: RawConfig,
+}
+
+#[derive(Args, Debug)]
 pub struct RawConfig {
     #[arg(short, long)]
     pub bin_path: String,
@@ -26,13 +93,87 @@ pub struct RawConfig {
     #[arg(short, long)]
     pub seed: Option<u64>,
 
+    /// JSON file of rules deciding which exit codes and stderr output count as findings
+    #[arg(long)]
+    pub oracles: Option<String>,
+
+    /// Replay crashes under gdb (or with core dumps enabled) to collect a backtrace
+    #[arg(long)]
+    pub debugger: bool,
+
+    /// Address space limit for the target in MB, too small for sanitizer builds
+    #[arg(long)]
+    pub memory_limit: Option<u64>,
+
+    /// Comma separated mutators to use, eg. `png::change_*,png::xor_crc=5`. A
+    /// trailing `*` matches a prefix and `=N` overrides the mutator's weight
+    #[arg(long, value_delimiter = ',')]
+    pub mutators: Vec<String>,
+
+    /// Comma separated mutators to never use, same syntax as --mutators
+    #[arg(long, value_delimiter = ',')]
+    pub disable_mutators: Vec<String>,
+
+    /// Command starting an external mutator process (eg. `python3 mutator.py`),
+    /// can be repeated. See src/external.rs for the protocol
+    #[arg(long)]
+    pub external_mutator: Vec<String>,
+
+    /// BNF or JSON (by its .json extension) grammar of the grammar fuzz type's
+    /// inputs. See src/grammar.rs for the syntax
+    #[arg(long)]
+    pub grammar: Option<String>,
+
+    /// cgroup v2 directory the target is moved into to detect the kernel killing it for memory
+    #[arg(long)]
+    pub cgroup: Option<String>,
+
+    /// AFL/libFuzzer style dictionary of tokens for the dictionary mutators, can be repeated
+    #[arg(long)]
+    pub dict: Vec<String>,
+
+    /// Don't extract tokens from the target's read-only data for the dictionary mutators
+    #[arg(long)]
+    pub no_auto_dict: bool,
+
+    /// Keep the mutator weights fixed instead of favouring mutators that find crashes
+    #[arg(long)]
+    pub static_schedule: bool,
+
+    /// Fewest mutations stacked on a seed for each random input
+    #[arg(long, default_value_t = 1)]
+    pub min_mutations: usize,
+
+    /// Most mutations stacked on a seed for each random input
+    #[arg(long, default_value_t = 4)]
+    pub max_mutations: usize,
+
+    /// Random inputs made from a seed before the next seed is picked
+    #[arg(long, default_value_t = 32)]
+    pub energy: usize,
+
+    /// Build of the target with comparison tracing (see runtime/cmplog.c), its
+    /// compared operands are substituted into each seed before mutation. AFL++
+    /// cmplog builds are not supported, they log to a shared memory map anomie
+    /// doesn't read
+    #[arg(long)]
+    pub cmplog: Option<String>,
+
+    /// Seeds larger than this many bytes skip the deterministic stage, 0 skips it for every seed
+    #[arg(long, default_value_t = 128)]
+    pub deterministic_max_len: usize,
+
     // everything after is part of args
     #[arg(last = true)]
     pub bin_args: String,
 }
 
 impl RawConfig {
-    pub fn validate(&self) -> Result<Config, anyhow::Error> {
+    // Checks what running the target needs: the binary, its args, the
+    // resource limits and the oracles deciding what its exits mean. The
+    // mutation settings are copied unchecked and the dictionaries, grammar
+    // and external mutators are left empty, see validate for fuzzing.
+    pub fn validate_target(&self) -> Result<Config, anyhow::Error> {
         // validate the binary passed in
         let metadata = fs::metadata(&self.bin_path)
             .map_err(|_| anyhow!("invalid binary path, double check the path exists"))?;
@@ -44,6 +185,8 @@ impl RawConfig {
         let validated_fuzz_type = match self.fuzz_type.to_lowercase().as_str() {
             "string" => FuzzType::String,
             "txt" => FuzzType::Txt,
+            "external" => FuzzType::External,
+            "grammar" => FuzzType::Grammar,
             "signedint" | "int" => FuzzType::SignedInt,
             "unsignedint" | "uint" => FuzzType::UnsignedInt,
             "jpeg" | "jpg" => FuzzType::Jpeg,
@@ -62,6 +205,19 @@ impl RawConfig {
         // parse the args and format them as a vector
         let bin_args: Vec<String> = self.bin_args.split(' ').map(String::from).collect();
 
+        let oracles = match &self.oracles {
+            Some(path) => oracle::load(path)?,
+            None => Vec::new(),
+        };
+
+        if let Some(cgroup) = &self.cgroup
+            && !Path::new(cgroup).join("memory.events").is_file()
+        {
+            return Err(anyhow!(
+                "{cgroup} is not a cgroup v2 directory with the memory controller enabled"
+            ));
+        }
+
         let temp_dir = tempdir().map_err(|_| anyhow!("can't create tempdir"))?;
         // let mutations_dir =
         //     tempdir_in(&temp_dir).map_err(|_| "can't create temporary directory for mutations")?;
@@ -69,14 +225,125 @@ impl RawConfig {
         //     tempdir_in(temp_dir).map_err(|_| "can't create temporary directory for corpus")?;
 
         Ok(Config {
-            bin_args: bin_args.clone(),
+            bin_args,
             bin_path: self.bin_path.clone(),
+            debugger: self.debugger,
+            cmplog: self.cmplog.clone(),
+            deterministic_max_len: self.deterministic_max_len,
+            dictionaries: self.dict.clone(),
+            dictionary: Dictionary::default(),
+            auto_dict: !self.no_auto_dict,
+            adaptive_schedule: !self.static_schedule,
+            auto_dictionary: Dictionary::default(),
             iterations: self.max_iterations,
+            min_mutations: self.min_mutations,
+            max_mutations: self.max_mutations,
+            energy: self.energy,
+            memory_limit: self.memory_limit,
+            mutators: MutatorSelection::default(),
+            external_mutators: self.external_mutator.clone(),
+            external: Vec::new(),
+            grammar_path: self.grammar.clone(),
+            grammar: None,
+            cgroup: self.cgroup.clone(),
+            oracles,
             report_path: self.report_path.clone(),
             rng,
             temp_dir,
             timeout: self.timeout,
-            validated_fuzz_type: validated_fuzz_type.clone(),
+            validated_fuzz_type,
+        })
+    }
+
+    // everything fuzzing needs on top of validate_target
+    pub fn validate(&self) -> Result<Config, anyhow::Error> {
+        let config = self.validate_target()?;
+        let validated_fuzz_type = &config.validated_fuzz_type;
+
+        if self.min_mutations == 0 || self.min_mutations > self.max_mutations {
+            return Err(anyhow!(
+                "--min-mutations must be at least 1 and at most --max-mutations"
+            ));
+        }
+        if self.energy == 0 {
+            return Err(anyhow!("--energy must be at least 1"));
+        }
+
+        if let Some(cmplog) = &self.cmplog
+            && !Path::new(cmplog).is_file()
+        {
+            return Err(anyhow!(
+                "invalid cmplog binary path, double check the path exists"
+            ));
+        }
+
+        // names are checked against the fuzz type's registry once fuzzing starts
+        let mutators = MutatorSelection {
+            enabled: self
+                .mutators
+                .iter()
+                .map(|s| MutatorPattern::parse(s))
+                .collect::<Result<_>>()?,
+            disabled: self
+                .disable_mutators
+                .iter()
+                .map(|s| MutatorPattern::parse(s))
+                .collect::<Result<_>>()?,
+        };
+
+        let mut external: Vec<Rc<ExternalMutator>> = Vec::new();
+        for command in &self.external_mutator {
+            let mutator = ExternalMutator::new(command)?;
+            if external.iter().any(|other| other.name == mutator.name) {
+                return Err(anyhow!("two external mutators are named {}", mutator.name));
+            }
+            external.push(Rc::new(mutator));
+        }
+        if matches!(validated_fuzz_type, FuzzType::External) && external.is_empty() {
+            return Err(anyhow!(
+                "the external fuzz type needs an --external-mutator"
+            ));
+        }
+
+        let grammar = self
+            .grammar
+            .as_deref()
+            .map(Grammar::load)
+            .transpose()?
+            .map(Rc::new);
+        match (validated_fuzz_type, &grammar) {
+            (FuzzType::Grammar, None) => {
+                return Err(anyhow!("the grammar fuzz type needs a --grammar"));
+            }
+            (FuzzType::Grammar, Some(_)) | (_, None) => {}
+            (_, Some(_)) => {
+                return Err(anyhow!("--grammar is only used by the grammar fuzz type"));
+            }
+        }
+
+        // the format's built-in tokens are added once fuzzing starts
+        let mut dictionary = Dictionary::default();
+        for path in &self.dict {
+            dictionary.extend(&Dictionary::load(path)?);
+        }
+
+        // scripts and non-ELF targets just go without
+        let auto_dictionary = if self.no_auto_dict {
+            Dictionary::default()
+        } else {
+            autodict::extract(&self.bin_path).unwrap_or_else(|e| {
+                warn!("Can't extract a dictionary from {}: {e}", self.bin_path);
+                Dictionary::default()
+            })
+        };
+
+        Ok(Config {
+            dictionary,
+            auto_dictionary,
+            mutators,
+            external,
+            grammar,
+            ..config
         })
     }
 }
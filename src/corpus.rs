@@ -0,0 +1,93 @@
+use rand::{Rng, rngs::SmallRng};
+
+use crate::mutate::MutationRecord;
+
+// A seed of the corpus, loaded once so splice mutators can combine inputs
+// with it
+pub struct CorpusEntry {
+    pub name: String,
+    pub content: Vec<u8>,
+    pub crc32: String,
+}
+
+impl CorpusEntry {
+    pub fn new(name: String, content: Vec<u8>) -> Self {
+        Self {
+            name,
+            crc32: format!("{:08x}", crc32fast::hash(&content)),
+            content,
+        }
+    }
+}
+
+// The corpus as mutators see it. A replay only knows the entries the recorded
+// splices used, the others are None but still count towards the length so
+// partners are drawn exactly as they were while fuzzing.
+#[derive(Default)]
+pub struct Corpus {
+    entries: Vec<Option<CorpusEntry>>,
+}
+
+// The corpus entry a splice used, as stored in its record
+pub struct RecordedPartner {
+    pub index: usize,
+    pub corpus_len: usize,
+    pub crc32: String,
+}
+
+impl Corpus {
+    pub fn new(entries: Vec<CorpusEntry>) -> Self {
+        Self {
+            entries: entries.into_iter().map(Some).collect(),
+        }
+    }
+
+    pub fn partial(len: usize, known: Vec<(usize, CorpusEntry)>) -> Self {
+        let mut entries: Vec<Option<CorpusEntry>> = (0..len).map(|_| None).collect();
+        for (index, entry) in known {
+            if let Some(slot) = entries.get_mut(index) {
+                *slot = Some(entry);
+            }
+        }
+        Self { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&CorpusEntry> {
+        self.entries.get(index)?.as_ref()
+    }
+
+    // picks an entry to splice with, None if it isn't known
+    pub fn choose_partner(&self, rng: &mut SmallRng) -> Option<(usize, &CorpusEntry)> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        let index = rng.random_range(0..self.entries.len());
+        Some((index, self.get(index)?))
+    }
+
+    // adds what a replay needs to find the partner again to a splice record
+    pub fn record_partner(&self, record: MutationRecord, index: usize) -> MutationRecord {
+        let record = record
+            .param("partner", index)
+            .param("corpus_len", self.len());
+        match self.get(index) {
+            Some(entry) => record
+                .param("partner_name", entry.name.as_str())
+                .param("partner_crc", entry.crc32.as_str()),
+            None => record,
+        }
+    }
+}
+
+pub fn recorded_partner(record: &MutationRecord) -> Option<RecordedPartner> {
+    let param = |name: &str| usize::try_from(record.params.get(name)?.as_u64()?).ok();
+    Some(RecordedPartner {
+        index: param("partner")?,
+        corpus_len: param("corpus_len")?,
+        crc32: record.params.get("partner_crc")?.as_str()?.to_string(),
+    })
+}
@@ -0,0 +1,218 @@
+use std::{
+    env, fs,
+    io::{Read, Seek},
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::LazyLock,
+    time::Duration,
+};
+
+use anyhow::Result;
+use log::{debug, info, warn};
+use regex::Regex;
+use tempfile::{tempdir_in, tempfile_in};
+use wait_timeout::ChildExt;
+
+use crate::{
+    analysis::Crash,
+    target::{file_args, string_args, target_command},
+    triage::StackFrame,
+    types::{Config, FuzzType},
+};
+
+// gdb is much slower than a bare run, give it some headroom over the target timeout
+const DEBUGGER_TIMEOUT: Duration = Duration::from_secs(30);
+// a stack overflow has thousands of frames, the innermost ones are enough
+const GDB_COMMANDS: [&str; 6] = [
+    "set pagination off",
+    "set confirm off",
+    "run",
+    "bt 64",
+    "info registers",
+    "x/i $pc",
+];
+
+static GDB_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^#\d+\s+(?:0x[0-9a-fA-F]+ in )?([^\s(]+) \(.*?\)(?: at ([^\s:]+):(\d+))?\s*$")
+        .unwrap()
+});
+static GDB_INSTRUCTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^=> (0x[0-9a-fA-F]+.*)$").unwrap());
+
+// What gdb told us about a crash
+pub struct DebuggerReport {
+    pub output: String,
+    pub frames: Vec<StackFrame>,
+    pub faulting_instruction: Option<String>,
+}
+
+// Re-runs a recorded crash to collect more context for uninstrumented targets.
+// If gdb is installed the input is replayed under gdb and the backtrace,
+// registers and faulting instruction are saved next to the crash as
+// `crash-N.gdb.txt`. Otherwise the input is replayed with core dumps enabled
+// and the core file is saved as `crash-N.core`.
+pub fn inspect(config: &Config, crash: &mut Crash) -> Result<()> {
+    let crash_path = PathBuf::from(&crash.file);
+
+    if gdb_available() {
+        let Some(report) = backtrace(config, &crash_path)? else {
+            return Ok(());
+        };
+
+        let output_path = crash_path.with_extension("gdb.txt");
+        fs::write(&output_path, &report.output)?;
+        debug!("Saved gdb output to {}", output_path.display());
+
+        // sanitizer frames are more precise, only fall back to gdb's
+        if crash.frames.is_empty() {
+            crash.frames = report.frames;
+        }
+        crash.faulting_instruction = report.faulting_instruction;
+        crash.debugger_output = Some(output_path.display().to_string());
+    } else if let Some(core_path) = collect_core(config, &crash_path)? {
+        crash.debugger_output = Some(core_path.display().to_string());
+    }
+
+    Ok(())
+}
+
+// replays a crash input under gdb, returns None if gdb is not installed
+pub fn backtrace(config: &Config, input_path: &Path) -> Result<Option<DebuggerReport>> {
+    if !gdb_available() {
+        return Ok(None);
+    }
+
+    // the output goes to a file rather than a pipe, gdb would block once a
+    // pipe is full and never finish
+    let mut output_file = tempfile_in(config.temp_dir.path())?;
+
+    // gdb passes its environment on to the target
+    let mut command = target_command("gdb");
+    command.args(["-q", "-batch", "-nx"]);
+    for gdb_command in GDB_COMMANDS {
+        command.args(["-ex", gdb_command]);
+    }
+    command
+        .arg("--args")
+        .arg(&config.bin_path)
+        .args(target_args(config, input_path)?)
+        .stdin(Stdio::null())
+        .stdout(output_file.try_clone()?)
+        .stderr(Stdio::null());
+
+    debug!("Replaying {} under gdb", input_path.display());
+    let mut child = command.spawn()?;
+    if child.wait_timeout(DEBUGGER_TIMEOUT)?.is_none() {
+        warn!("gdb timed out on {}", input_path.display());
+        child.kill()?;
+        child.wait()?;
+        return Ok(None);
+    }
+
+    // the target writes to the same file, its output isn't necessarily utf-8
+    let mut output = Vec::new();
+    output_file.rewind()?;
+    output_file.read_to_end(&mut output)?;
+    let output = String::from_utf8_lossy(&output).into_owned();
+
+    let frames = GDB_FRAME
+        .captures_iter(&output)
+        .map(|c| StackFrame {
+            function: c[1].to_string(),
+            file: c.get(2).map(|m| m.as_str().to_string()),
+            line: c.get(3).and_then(|m| m.as_str().parse().ok()),
+            column: None,
+        })
+        .collect();
+    let faulting_instruction = GDB_INSTRUCTION
+        .captures(&output)
+        .map(|c| c[1].trim().to_string());
+
+    Ok(Some(DebuggerReport {
+        output,
+        frames,
+        faulting_instruction,
+    }))
+}
+
+// replays a crash input with the core size limit lifted, then moves whatever
+// core file the kernel wrote next to the crash
+fn collect_core(config: &Config, input_path: &Path) -> Result<Option<PathBuf>> {
+    let core_pattern = fs::read_to_string("/proc/sys/kernel/core_pattern").unwrap_or_default();
+    if core_pattern.starts_with('|') {
+        info!(
+            "Core dumps are piped to `{}`, use your system's core handler (eg. coredumpctl) to retrieve them",
+            core_pattern.trim()
+        );
+        return Ok(None);
+    }
+
+    // run inside an empty dir so a relative core_pattern lands somewhere we can find it
+    let work_dir = tempdir_in(config.temp_dir.path())?;
+    let mut command = target_command(&config.bin_path);
+    command
+        .args(target_args(config, input_path)?)
+        .current_dir(work_dir.path())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    // raising the hard limit needs CAP_SYS_RESOURCE, the soft limit goes
+    // as high as the hard one allows
+    // SAFETY: getrlimit and setrlimit are async-signal-safe and only touch the child's limits
+    unsafe {
+        command.pre_exec(|| {
+            let mut limit = libc::rlimit {
+                rlim_cur: 0,
+                rlim_max: 0,
+            };
+            if libc::getrlimit(libc::RLIMIT_CORE, &raw mut limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            limit.rlim_cur = limit.rlim_max;
+            if libc::setrlimit(libc::RLIMIT_CORE, &raw const limit) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    let mut child = command.spawn()?;
+    if child
+        .wait_timeout(Duration::from_millis(config.timeout))?
+        .is_none()
+    {
+        child.kill()?;
+        child.wait()?;
+        return Ok(None);
+    }
+
+    for entry in fs::read_dir(work_dir.path())? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with("core") {
+            let core_path = input_path.with_extension("core");
+            fs::copy(entry.path(), &core_path)?;
+            debug!("Saved core file to {}", core_path.display());
+            return Ok(Some(core_path));
+        }
+    }
+
+    debug!(
+        "No core file was written for {}, check `ulimit -c` and /proc/sys/kernel/core_pattern",
+        input_path.display()
+    );
+    Ok(None)
+}
+
+fn target_args(config: &Config, input_path: &Path) -> Result<Vec<String>> {
+    match config.validated_fuzz_type {
+        FuzzType::String => Ok(string_args(config, &fs::read(input_path)?)),
+        _ => file_args(config, input_path),
+    }
+}
+
+fn gdb_available() -> bool {
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| dir.join("gdb").is_file()))
+}
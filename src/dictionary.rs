@@ -0,0 +1,166 @@
+use std::{fmt::Write, fs, path::Path};
+
+use anyhow::{Result, anyhow};
+use rand::{rngs::SmallRng, seq::IndexedRandom};
+
+// where the tokens a campaign used are saved in its report
+pub const DICTIONARY_FILE: &str = "dictionary.dict";
+
+// Tokens the dictionary mutators insert into inputs or overwrite them with,
+// eg. magic values and keywords random bytes are unlikely to hit
+#[derive(Debug, Clone, Default)]
+pub struct Dictionary {
+    tokens: Vec<Vec<u8>>,
+}
+
+impl Dictionary {
+    pub fn from_tokens(tokens: &[&[u8]]) -> Self {
+        let mut dictionary = Self::default();
+        for token in tokens {
+            dictionary.add(token);
+        }
+        dictionary
+    }
+
+    // reads an AFL/libFuzzer dictionary file, eg.
+    // # comment
+    // header_png="\x89PNG"
+    // "IHDR"
+    pub fn load(path: &str) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| anyhow!("can't read dictionary {path}: {e}"))?;
+        Self::parse(&contents).map_err(|e| anyhow!("invalid dictionary {path}: {e}"))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut dictionary = Self::default();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            // the name (and its optional @level) before the value is ignored
+            let (Some(start), Some(end)) = (line.find('"'), line.rfind('"')) else {
+                return Err(anyhow!("line {}: expected a quoted token", i + 1));
+            };
+            if start == end || !line.ends_with('"') {
+                return Err(anyhow!("line {}: unterminated token", i + 1));
+            }
+            let token =
+                unescape(&line[start + 1..end]).map_err(|e| anyhow!("line {}: {e}", i + 1))?;
+            dictionary.add(&token);
+        }
+        Ok(dictionary)
+    }
+
+    // empty and duplicate tokens are ignored
+    pub fn add(&mut self, token: &[u8]) {
+        if !token.is_empty() && !self.tokens.iter().any(|t| t == token) {
+            self.tokens.push(token.to_vec());
+        }
+    }
+
+    pub fn extend(&mut self, other: &Dictionary) {
+        for token in &other.tokens {
+            self.add(token);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn choose(&self, rng: &mut SmallRng) -> Option<&[u8]> {
+        self.tokens.choose(rng).map(Vec::as_slice)
+    }
+
+    // picks one of the tokens that are exactly `len` bytes long, for fixed
+    // size fields like png chunk types
+    pub fn choose_len(&self, rng: &mut SmallRng, len: usize) -> Option<&[u8]> {
+        let matching: Vec<&Vec<u8>> = self.tokens.iter().filter(|t| t.len() == len).collect();
+        matching.choose(rng).map(|token| token.as_slice())
+    }
+
+    // writes the tokens in the same format `load` reads
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut s = String::new();
+        for token in &self.tokens {
+            writeln!(&mut s, "\"{}\"", escape(token))?;
+        }
+        fs::write(path, s)?;
+        Ok(())
+    }
+}
+
+// printable ascii is kept as is, everything else is written as \xNN
+pub fn escape(token: &[u8]) -> String {
+    let mut s = String::new();
+    for &byte in token {
+        match byte {
+            b'\\' | b'"' => {
+                s.push('\\');
+                s.push(byte as char);
+            }
+            0x20..=0x7E => s.push(byte as char),
+            _ => {
+                let _ = write!(&mut s, "\\x{byte:02x}");
+            }
+        }
+    }
+    s
+}
+
+// reverses escape, also used to read tokens back from mutation records
+pub fn unescape(value: &str) -> Result<Vec<u8>> {
+    let mut token = Vec::new();
+    let mut bytes = value.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte != b'\\' {
+            token.push(byte);
+            continue;
+        }
+        match bytes.next() {
+            Some(b'\\') => token.push(b'\\'),
+            Some(b'"') => token.push(b'"'),
+            Some(b'x') => {
+                let hex: Vec<u8> = bytes.by_ref().take(2).collect();
+                let byte = std::str::from_utf8(&hex)
+                    .ok()
+                    .filter(|hex| hex.len() == 2)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| anyhow!("invalid \\x escape"))?;
+                token.push(byte);
+            }
+            _ => return Err(anyhow!("invalid escape sequence")),
+        }
+    }
+    Ok(token)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaped_tokens_round_trip() {
+        let token: Vec<u8> = (0..=255).collect();
+        let escaped = escape(&token);
+        assert!(escaped.bytes().all(|b| (0x20..=0x7E).contains(&b)));
+        assert_eq!(unescape(&escaped).unwrap(), token);
+
+        let dictionary = Dictionary::parse(&format!("name@1=\"{escaped}\"\n")).unwrap();
+        assert_eq!(dictionary.tokens, vec![token]);
+    }
+
+    #[test]
+    fn invalid_escapes_are_rejected() {
+        assert!(unescape("\\x4").is_err());
+        assert!(unescape("\\xzz").is_err());
+        assert!(unescape("\\n").is_err());
+    }
+}
@@ -0,0 +1,191 @@
+use anyhow::Result;
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write,
+    fs,
+    path::Path,
+};
+
+use crate::{analysis::Crash, config::DiffArgs};
+
+// the parts of a previous run's crashes.json that are compared
+#[derive(Deserialize)]
+struct RecordedRun {
+    crashes: Vec<Crash>,
+    stats: Map<String, Value>,
+}
+
+#[derive(Serialize)]
+pub struct SignatureCount {
+    pub signature: String,
+    pub old: usize,
+    pub new: usize,
+}
+
+#[derive(Serialize)]
+pub struct ValueChange {
+    pub key: String,
+    pub old: Value,
+    pub new: Value,
+}
+
+// Result of comparing two runs. Signatures only found in the new run are
+// "new", ones only found in the old run have "disappeared".
+#[derive(Serialize)]
+pub struct RunDiff {
+    pub old_report: String,
+    pub new_report: String,
+    pub new: Vec<SignatureCount>,
+    pub disappeared: Vec<SignatureCount>,
+    pub common: Vec<SignatureCount>,
+    pub stats: Vec<ValueChange>,
+    pub config: Vec<ValueChange>,
+}
+
+pub fn run_diff(args: &DiffArgs) -> Result<()> {
+    let old_dir = Path::new(&args.old);
+    let new_dir = Path::new(&args.new);
+
+    let old_run: RecordedRun = read_json(old_dir, "crashes.json")?;
+    let new_run: RecordedRun = read_json(new_dir, "crashes.json")?;
+    let old_config: Map<String, Value> = read_json(old_dir, "config.json")?;
+    let new_config: Map<String, Value> = read_json(new_dir, "config.json")?;
+
+    let old_counts = count_signatures(&old_run.crashes);
+    let new_counts = count_signatures(&new_run.crashes);
+
+    let mut diff = RunDiff {
+        old_report: args.old.clone(),
+        new_report: args.new.clone(),
+        new: Vec::new(),
+        disappeared: Vec::new(),
+        common: Vec::new(),
+        stats: compare_maps(&old_run.stats, &new_run.stats),
+        config: compare_maps(&old_config, &new_config),
+    };
+
+    let signatures: BTreeSet<&String> = old_counts.keys().chain(new_counts.keys()).collect();
+    for signature in signatures {
+        let count = SignatureCount {
+            signature: signature.clone(),
+            old: old_counts.get(signature).copied().unwrap_or_default(),
+            new: new_counts.get(signature).copied().unwrap_or_default(),
+        };
+        match (count.old, count.new) {
+            (0, _) => diff.new.push(count),
+            (_, 0) => diff.disappeared.push(count),
+            _ => diff.common.push(count),
+        }
+    }
+
+    fs::write(&args.json, serde_json::to_string(&diff)?)?;
+    print_diff(&diff, &args.json)?;
+    Ok(())
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(report_dir: &Path, name: &str) -> Result<T> {
+    let contents = fs::read_to_string(report_dir.join(name))?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+fn count_signatures(crashes: &[Crash]) -> BTreeMap<String, usize> {
+    let mut counts = BTreeMap::new();
+    for crash in crashes {
+        *counts.entry(crash.signature()).or_default() += 1;
+    }
+    counts
+}
+
+// lists every key whose value differs, keys missing on one side show up as
+// null. objects are compared key by key, eg. `signals.SIGSEGV`
+fn compare_maps(old: &Map<String, Value>, new: &Map<String, Value>) -> Vec<ValueChange> {
+    let mut changes = Vec::new();
+    compare_objects("", old, new, &mut changes);
+    changes
+}
+
+fn compare_objects(
+    prefix: &str,
+    old: &Map<String, Value>,
+    new: &Map<String, Value>,
+    changes: &mut Vec<ValueChange>,
+) {
+    let empty = Map::new();
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let path = format!("{prefix}{key}");
+        let old_value = old.get(key).unwrap_or(&Value::Null);
+        let new_value = new.get(key).unwrap_or(&Value::Null);
+        match (old_value, new_value) {
+            (Value::Object(old), Value::Object(new)) => {
+                compare_objects(&format!("{path}."), old, new, changes);
+            }
+            (Value::Object(old), Value::Null) => {
+                compare_objects(&format!("{path}."), old, &empty, changes);
+            }
+            (Value::Null, Value::Object(new)) => {
+                compare_objects(&format!("{path}."), &empty, new, changes);
+            }
+            _ if old_value != new_value => changes.push(ValueChange {
+                key: path,
+                old: old_value.clone(),
+                new: new_value.clone(),
+            }),
+            _ => {}
+        }
+    }
+}
+
+fn print_diff(diff: &RunDiff, json_path: &str) -> Result<()> {
+    let mut s = String::new();
+
+    writeln!(&mut s, "\n=====RUN COMPARISON=====")?;
+    writeln!(&mut s, "{} -> {}", diff.old_report, diff.new_report)?;
+    writeln!(&mut s, "summary written to \n  `{json_path}`")?;
+    writeln!(&mut s, "new signatures:         {}", diff.new.len())?;
+    writeln!(&mut s, "disappeared signatures: {}", diff.disappeared.len())?;
+    writeln!(&mut s, "common signatures:      {}", diff.common.len())?;
+
+    for count in &diff.new {
+        writeln!(
+            &mut s,
+            "  [NEW]         {} ({})",
+            count.signature, count.new
+        )?;
+    }
+    for count in &diff.disappeared {
+        writeln!(
+            &mut s,
+            "  [DISAPPEARED] {} ({})",
+            count.signature, count.old
+        )?;
+    }
+    for count in &diff.common {
+        writeln!(
+            &mut s,
+            "  [COMMON]      {} ({} -> {})",
+            count.signature, count.old, count.new
+        )?;
+    }
+
+    if !diff.stats.is_empty() {
+        writeln!(&mut s, "-----STATS-----")?;
+        for change in &diff.stats {
+            writeln!(&mut s, "  {}: {} -> {}", change.key, change.old, change.new)?;
+        }
+    }
+    if !diff.config.is_empty() {
+        writeln!(&mut s, "-----CONFIG-----")?;
+        for change in &diff.config {
+            writeln!(&mut s, "  {}: {} -> {}", change.key, change.old, change.new)?;
+        }
+    }
+    write!(&mut s, "========================")?;
+
+    info!("{s}");
+
+    Ok(())
+}
@@ -1,15 +1,30 @@
-use std::fs::{self, File, read_dir};
+use std::collections::{HashSet, VecDeque};
+use std::fs::{self, DirEntry, File, read_dir};
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::io::Write;
 use std::marker::PhantomData;
+use std::path::Path;
+use std::rc::Rc;
 
-use anyhow::Result;
-use log::{debug, info};
+use anyhow::{Result, anyhow};
+use log::{debug, info, warn};
 use rand::Rng;
 
-use crate::analysis::CrashAnalyzer;
-use crate::errors::ExitStatus;
+use crate::analysis::{CrashAnalyzer, Seed};
+use crate::autodict::AUTO_DICTIONARY_FILE;
+use crate::cmplog;
+use crate::corpus::{Corpus, CorpusEntry, recorded_partner};
+use crate::debugger;
+use crate::dictionary::{DICTIONARY_FILE, Dictionary};
 use crate::formats::template::FileFormat;
-use crate::target::{run_target_file, run_target_string};
+use crate::grammar::GRAMMAR_FILE;
+use crate::mutate::MutationRecord;
+use crate::mutations::cmplog::{CmpLogStage, MAX_DEPTH};
+use crate::mutations::deterministic::DeterministicStage;
+use crate::mutations::schedule::Scheduler;
+use crate::mutations::{MutationContext, MutatorRegistry, external};
+use crate::target::{file_args, run_target_file, run_target_string, string_args};
+use crate::triage::Severity;
 use crate::types::{Config, FuzzType, StructuredInput};
 use crate::utils::filename_bytes;
 
@@ -19,6 +34,23 @@ pub struct Engine<'a, F: FileFormat> {
     _marker: PhantomData<F>,
 }
 
+// random inputs a seed can't be mutated into something new after this many
+// tries are skipped
+const MAX_MUTATION_ATTEMPTS: usize = 64;
+
+// The inputs a seed goes through before random mutation, the cmplog
+// substitutions then the deterministic stage
+struct SeedStage {
+    idx: usize,
+    cmplog: Option<CmpLogStage>,
+    deterministic: Option<DeterministicStage>,
+    budget: usize, // staged inputs the seed can still make
+}
+
+// the index of the seed a staged input was made from, the input and the
+// mutations that made it
+type StagedInput = (usize, Vec<u8>, Vec<MutationRecord>);
+
 pub fn run_engine_for<T: FileFormat>(
     analyzer: &mut CrashAnalyzer,
     config: &mut Config,
@@ -41,35 +73,143 @@ impl<'a, F: FileFormat> Engine<'a, F> {
 
         let corpus_dir = self.config.temp_dir.path().join("corpus");
         let mutations_dir = self.config.temp_dir.path().join("mutations");
-        F::generate_corpus(&mut self.config.rng, &corpus_dir)?;
+        self.generate_corpus(&corpus_dir)?;
 
-        let corpus: Vec<_> = read_dir(&corpus_dir)?.filter_map(Result::ok).collect();
+        if !self.config.auto_dictionary.is_empty() {
+            info!(
+                "Extracted {} dictionary tokens from {}",
+                self.config.auto_dictionary.len(),
+                self.config.bin_path
+            );
+            self.config
+                .auto_dictionary
+                .save(&Path::new(&self.config.report_path).join(AUTO_DICTIONARY_FILE))?;
+        }
+
+        // the format's built-in tokens, the user's and the target's. saved
+        // with the report since replaying a token mutation needs the same tokens
+        let mut dictionary = Dictionary::from_tokens(F::DICTIONARY);
+        dictionary.extend(&self.config.dictionary);
+        dictionary.extend(&self.config.auto_dictionary);
+        if !dictionary.is_empty() {
+            info!("Using {} dictionary tokens", dictionary.len());
+            dictionary.save(&Path::new(&self.config.report_path).join(DICTIONARY_FILE))?;
+        }
+
+        // the corpus is loaded once, splice mutators combine inputs with it
+        let mut entries = Vec::new();
+        for entry in read_dir(&corpus_dir)? {
+            let entry = entry?;
+            let content = self.seed_content(&entry)?;
+            entries.push(CorpusEntry::new(
+                entry.file_name().to_string_lossy().into_owned(),
+                content,
+            ));
+        }
+        let context = Rc::new(MutationContext {
+            dictionary,
+            corpus: Corpus::new(entries),
+            external: self.config.external.clone(),
+            grammar: self.config.grammar.clone(),
+        });
+        let corpus = &context.corpus;
         let corpus_size = corpus.len();
 
-        for i in 0..self.config.iterations {
-            let rand_idx = self.config.rng.random_range(0..corpus_size);
-            let random_file = &corpus[rand_idx];
+        let mutators = external::mutators::<F>(&context).configure(&self.config.mutators)?;
+        let mut scheduler = Scheduler::new(&mutators, self.config.adaptive_schedule);
 
-            let content: &[u8] = match self.config.validated_fuzz_type {
-                FuzzType::String => &filename_bytes(random_file),
-                FuzzType::Txt | FuzzType::Jpeg | FuzzType::Png => &fs::read(random_file.path())?,
-                _ => unreachable!(),
+        // every seed goes through the cmplog and deterministic stages before
+        // random mutation starts. they get at most half of the iterations,
+        // split between the seeds so one large seed can't use them all
+        let mut pending: VecDeque<usize> = (0..corpus_size).collect();
+        let mut stage: Option<SeedStage> = None;
+        let mut staged_left = self.config.iterations / 2;
+
+        // hashes of every input executed so far, the seeds are included so
+        // an unmodified seed is never run
+        let mut executed: HashSet<u64> = (0..corpus_size)
+            .filter_map(|idx| corpus.get(idx))
+            .map(|entry| input_hash(&entry.content))
+            .collect();
+        let mut duplicates = 0;
+        // the seed random inputs are made from and how many are left to make
+        let mut rand_idx = 0;
+        let mut energy = 0;
+
+        for i in 0..self.config.iterations {
+            let staged = loop {
+                if staged_left == 0 {
+                    break None;
+                }
+                match self.next_staged(corpus, &mut pending, &mut stage, staged_left)? {
+                    Some((_, mutated_bytes, _)) if !executed.insert(input_hash(&mutated_bytes)) => {
+                        duplicates += 1;
+                    }
+                    staged => break staged,
+                }
             };
+            if staged.is_some() {
+                staged_left -= 1;
+                if staged_left == 0 && (stage.is_some() || !pending.is_empty()) {
+                    info!("Used half of the iterations on staged inputs, the rest are random");
+                }
+            }
 
-            // mutate input
-            let mut mutation_array: Vec<String> = Vec::new();
-            let mutation_count = self.config.rng.random_range(0..5);
-            let mut model: F::Model = F::parse(content)?;
-            for _ in 0..mutation_count {
-                let mutation_string = F::mutate(&mut self.config.rng, &mut model)?;
-                debug!("{mutation_string}");
-                mutation_array.push(mutation_string);
+            // `applied` holds the registry index of every mutator the input
+            // was made with, for the scheduler
+            let (seed_idx, mutated_bytes, mutation_array, applied) = match staged {
+                Some((idx, mutated_bytes, mutation_array)) => {
+                    (idx, mutated_bytes, mutation_array, Vec::new())
+                }
+                None => {
+                    if energy == 0 {
+                        rand_idx = self.config.rng.random_range(0..corpus_size);
+                        energy = self.config.energy;
+                    }
+                    energy -= 1;
+
+                    // mutations that don't apply or land back on an input
+                    // that already ran are tried again
+                    let content = &corpus_entry(corpus, rand_idx)?.content;
+                    let mut mutated = None;
+                    for _ in 0..MAX_MUTATION_ATTEMPTS {
+                        let (mutated_bytes, mutation_array, applied) =
+                            self.mutate(&mutators, &scheduler, content)?;
+                        if !mutation_array.is_empty() && executed.insert(input_hash(&mutated_bytes))
+                        {
+                            mutated = Some((rand_idx, mutated_bytes, mutation_array, applied));
+                            break;
+                        }
+                        duplicates += 1;
+                    }
+                    let Some(mutated) = mutated else {
+                        debug!(
+                            "No new input could be made from {} in {MAX_MUTATION_ATTEMPTS} attempts",
+                            corpus_entry(corpus, rand_idx)?.name
+                        );
+                        continue;
+                    };
+                    mutated
+                }
+            };
+            for mutation in &mutation_array {
+                debug!("{mutation}");
             }
 
-            let mutated_bytes = F::generate(model)?;
+            let seed_entry = corpus_entry(corpus, seed_idx)?;
+            let seed = Seed::new(
+                &seed_entry.name,
+                &seed_entry.content,
+                &self.config.report_path,
+                F::EXT,
+            );
 
             let (structured_input, result) = match self.config.validated_fuzz_type {
-                FuzzType::Txt | FuzzType::Jpeg | FuzzType::Png => {
+                FuzzType::Txt
+                | FuzzType::Jpeg
+                | FuzzType::Png
+                | FuzzType::External
+                | FuzzType::Grammar => {
                     let mutated_file_name = format!("{i}.{}", F::EXT);
                     let mut mutated_file = File::create(mutations_dir.join(&mutated_file_name))?;
                     mutated_file.write_all(&mutated_bytes)?;
@@ -79,21 +219,286 @@ impl<'a, F: FileFormat> Engine<'a, F> {
                             extension: F::EXT.to_string(),
                         },
                         run_target_file(self.config, mutated_file_name.as_str())
-                            .unwrap_or(ExitStatus::ExitCode(0)),
+                            .map_err(|e| anyhow!("can't run the target: {e}"))?,
                     )
                 }
                 // unique handling for fuzzing the filename itself
                 FuzzType::String => (
                     StructuredInput::StringInput(mutated_bytes.clone()),
                     run_target_string(self.config, &mutated_bytes)
-                        .unwrap_or(ExitStatus::ExitCode(0)),
+                        .map_err(|e| anyhow!("can't run the target: {e}"))?,
                 ),
                 _ => unreachable!(),
             };
 
+            let status = result.status.clone();
+            let crash_count = self.analyzer.crashes.len();
             self.analyzer
                 .analyze(i, result, structured_input, mutation_array)?;
+
+            if self.analyzer.crashes.len() > crash_count
+                && let Some(crash) = self.analyzer.crashes.last_mut()
+            {
+                // keep the seed the crash was mutated from, and the entries it
+                // was spliced with, so it can be replayed
+                seed.save(&seed_entry.content)?;
+                crash.seed = Some(seed);
+                if let Some(grammar) = &context.grammar {
+                    crash.derivation = grammar
+                        .parse(&mutated_bytes)
+                        .map(|node| grammar.derivation(&node));
+                }
+                for partner in crash.mutations.iter().filter_map(recorded_partner) {
+                    if let Some(entry) = corpus.get(partner.index) {
+                        Seed::new(
+                            &entry.name,
+                            &entry.content,
+                            &self.config.report_path,
+                            F::EXT,
+                        )
+                        .save(&entry.content)?;
+                    }
+                }
+
+                // no sanitizer backtrace was found. hangs are skipped since
+                // they would hang gdb too
+                if self.config.debugger
+                    && crash.frames.is_empty()
+                    && crash.severity != Severity::Hang
+                {
+                    // a missing or failing gdb shouldn't end the campaign
+                    if let Err(e) = debugger::inspect(self.config, crash) {
+                        warn!("Can't inspect {} with the debugger: {e}", crash.file);
+                    }
+                }
+
+                crash.write_sidecar()?;
+            }
+
+            let crashed = self.analyzer.crashes.len() > crash_count;
+            let outcome = match self.analyzer.crashes.last() {
+                Some(crash) if crashed => crash.signature(),
+                _ => format!("{status:?}"),
+            };
+            scheduler.record(&mut self.config.rng, &applied, crashed, &outcome);
         }
+
+        if duplicates > 0 {
+            info!("Skipped {duplicates} inputs that were unchanged or already executed");
+        }
+        self.analyzer.mutators = scheduler.stats();
         Ok(())
     }
+
+    // stacks a random number of mutations on a seed, returns the input along
+    // with the records and registry indices of the mutations that applied
+    fn mutate(
+        &mut self,
+        mutators: &MutatorRegistry<F::Model>,
+        scheduler: &Scheduler,
+        content: &[u8],
+    ) -> Result<(Vec<u8>, Vec<MutationRecord>, Vec<usize>)> {
+        let mut mutation_array: Vec<MutationRecord> = Vec::new();
+        let mut applied = Vec::new();
+        let mutation_count = self
+            .config
+            .rng
+            .random_range(self.config.min_mutations..=self.config.max_mutations);
+        let mut model: F::Model = F::parse(content)?;
+        for _ in 0..mutation_count {
+            let mutator_idx = scheduler.choose(&mut self.config.rng);
+            let mutator = &mutators.mutators()[mutator_idx];
+            let rng_seed = self.config.rng.random();
+            if let Some(mutation) = mutator.apply(&mut model, rng_seed)? {
+                mutation_array.push(mutation);
+                applied.push(mutator_idx);
+            }
+        }
+        Ok((F::generate(model)?, mutation_array, applied))
+    }
+
+    // the external fuzz type's seeds come from its mutator and the grammar
+    // fuzz type's from its grammar, the other formats generate their own
+    fn generate_corpus(&mut self, corpus_dir: &Path) -> Result<()> {
+        if let Some(grammar) = &self.config.grammar {
+            info!(
+                "Generating the corpus from {} nonterminals",
+                grammar.nonterminals()
+            );
+            // saved with the report since replay parses the seeds with it
+            grammar.save(&Path::new(&self.config.report_path).join(GRAMMAR_FILE))?;
+            return grammar.generate_corpus(&mut self.config.rng, corpus_dir, F::EXT);
+        }
+        if let (FuzzType::External, Some(external)) = (
+            &self.config.validated_fuzz_type,
+            self.config.external.first(),
+        ) {
+            // random seeds are used when the mutator can't provide any
+            let seeds = external.seeds().unwrap_or_else(|e| {
+                warn!("{e}");
+                Vec::new()
+            });
+            info!("{} provided {} seeds", external.name, seeds.len());
+            for (i, (name, content)) in seeds.into_iter().enumerate() {
+                // names only become file names, keep them inside the corpus
+                let name = Path::new(&name).file_name().map_or_else(
+                    || format!("{i}.{}", F::EXT),
+                    |name| name.to_string_lossy().into_owned(),
+                );
+                fs::write(corpus_dir.join(name), content)?;
+            }
+            if read_dir(corpus_dir)?.next().is_some() {
+                return Ok(());
+            }
+        }
+        F::generate_corpus(&mut self.config.rng, corpus_dir)
+    }
+
+    fn seed_content(&self, entry: &DirEntry) -> Result<Vec<u8>> {
+        Ok(match self.config.validated_fuzz_type {
+            FuzzType::String => filename_bytes(entry),
+            FuzzType::Txt
+            | FuzzType::Jpeg
+            | FuzzType::Png
+            | FuzzType::External
+            | FuzzType::Grammar => fs::read(entry.path())?,
+            _ => unreachable!(),
+        })
+    }
+
+    // the next staged input along with the index of the seed it was made
+    // from, moving on to the next pending seed once a stage is done. None
+    // once every seed went through them
+    fn next_staged(
+        &self,
+        corpus: &Corpus,
+        pending: &mut VecDeque<usize>,
+        stage: &mut Option<SeedStage>,
+        staged_left: usize,
+    ) -> Result<Option<StagedInput>> {
+        loop {
+            if let Some(seed_stage) = stage.as_mut() {
+                if seed_stage.budget == 0 {
+                    info!(
+                        "{} used its share of the staged inputs, skipping the rest of its stages",
+                        corpus_entry(corpus, seed_stage.idx)?.name
+                    );
+                    *stage = None;
+                    continue;
+                }
+                seed_stage.budget -= 1;
+            }
+            if let Some(SeedStage {
+                idx,
+                cmplog: Some(cmplog),
+                ..
+            }) = stage
+                && let Some((mutated_bytes, mutation_array)) = cmplog.next()
+            {
+                // see what the target compares once these substitutions are
+                // made, new comparisons are solved on top of them
+                if let Some(cmplog_path) = &self.config.cmplog
+                    && mutation_array.len() < MAX_DEPTH
+                {
+                    let comparisons = self.trace_comparisons(cmplog_path, &mutated_bytes);
+                    cmplog.add(mutated_bytes.clone(), mutation_array.clone(), &comparisons);
+                }
+                return Ok(Some((*idx, mutated_bytes, mutation_array)));
+            }
+            if let Some(SeedStage {
+                idx,
+                deterministic: Some(deterministic),
+                ..
+            }) = stage
+                && let Some((mutated_bytes, mutation)) = deterministic.next()
+            {
+                return Ok(Some((*idx, mutated_bytes, vec![mutation])));
+            }
+            *stage = None;
+
+            let Some(idx) = pending.pop_front() else {
+                return Ok(None);
+            };
+            let Some(entry) = corpus.get(idx) else {
+                continue;
+            };
+            if entry.content.is_empty() {
+                continue;
+            }
+
+            let cmplog = match &self.config.cmplog {
+                Some(cmplog_path) => {
+                    let comparisons = self.trace_comparisons(cmplog_path, &entry.content);
+                    info!(
+                        "Logged {} comparisons made on {}",
+                        comparisons.len(),
+                        entry.name
+                    );
+                    let mut cmplog = CmpLogStage::default();
+                    cmplog.add(entry.content.clone(), Vec::new(), &comparisons);
+                    Some(cmplog)
+                }
+                None => None,
+            };
+            let deterministic = if entry.content.len() <= self.config.deterministic_max_len {
+                info!(
+                    "Running the deterministic stage on {} ({} bytes)",
+                    entry.name,
+                    entry.content.len()
+                );
+                Some(DeterministicStage::new(entry.content.clone()))
+            } else {
+                None
+            };
+            // what earlier seeds didn't use is shared by the ones left
+            *stage = Some(SeedStage {
+                idx,
+                cmplog,
+                deterministic,
+                budget: (staged_left / (pending.len() + 1)).max(1),
+            });
+        }
+    }
+
+    // runs the comparison tracing build of the target on a seed. a run that
+    // fails or leaves a corrupt log only costs that input its comparisons
+    fn trace_comparisons(&self, cmplog_path: &str, content: &[u8]) -> Vec<cmplog::Comparison> {
+        let traced = match self.config.validated_fuzz_type {
+            FuzzType::Txt
+            | FuzzType::Jpeg
+            | FuzzType::Png
+            | FuzzType::External
+            | FuzzType::Grammar => {
+                let path = self
+                    .config
+                    .temp_dir
+                    .path()
+                    .join("mutations")
+                    .join(format!("cmplog.{}", F::EXT));
+                fs::write(&path, content)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|()| file_args(self.config, &path))
+            }
+            FuzzType::String => Ok(string_args(self.config, content)),
+            _ => unreachable!(),
+        }
+        .and_then(|args| cmplog::trace(self.config, cmplog_path, args));
+        traced.unwrap_or_else(|e| {
+            warn!("Can't trace the comparisons of an input: {e}");
+            Vec::new()
+        })
+    }
+}
+
+fn input_hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+// the fuzzer's own corpus has every entry loaded
+fn corpus_entry(corpus: &Corpus, index: usize) -> Result<&CorpusEntry> {
+    corpus
+        .get(index)
+        .ok_or_else(|| anyhow!("corpus entry {index} isn't loaded"))
 }
@@ -1,4 +1,7 @@
-#[derive(Debug, PartialEq, Eq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
 pub enum ExitStatus {
     ExitCode(i32),
     Signal(i32),
@@ -6,9 +9,52 @@ pub enum ExitStatus {
     Timeout(u128),
 }
 
-pub const SIGILL: i32 = 4; // abnormal termination
-pub const SIGABRT: i32 = 6; // abnormal termination
-pub const SIGFPE: i32 = 8; // floating point exception
-pub const SIGSEGV: i32 = 11; // seg fault
-pub const SIGPIPE: i32 = 13; // pipe error
-pub const SIGTERM: i32 = 15; // termination signal
+// name and short description of every standard signal
+const SIGNALS: [(i32, &str, &str); 30] = [
+    (libc::SIGHUP, "SIGHUP", "hangup"),
+    (libc::SIGINT, "SIGINT", "interrupt"),
+    (libc::SIGQUIT, "SIGQUIT", "quit"),
+    (libc::SIGILL, "SIGILL", "illegal instruction"),
+    (libc::SIGTRAP, "SIGTRAP", "trace/breakpoint trap"),
+    (libc::SIGABRT, "SIGABRT", "abort function"),
+    (libc::SIGBUS, "SIGBUS", "bus error"),
+    (libc::SIGFPE, "SIGFPE", "floating point exception"),
+    (libc::SIGKILL, "SIGKILL", "killed"),
+    (libc::SIGUSR1, "SIGUSR1", "user defined signal 1"),
+    (libc::SIGSEGV, "SIGSEGV", "segmentation fault"),
+    (libc::SIGUSR2, "SIGUSR2", "user defined signal 2"),
+    (libc::SIGPIPE, "SIGPIPE", "pipe error"),
+    (libc::SIGALRM, "SIGALRM", "alarm clock"),
+    (libc::SIGTERM, "SIGTERM", "termination error"),
+    (libc::SIGSTKFLT, "SIGSTKFLT", "stack fault"),
+    (libc::SIGCHLD, "SIGCHLD", "child exited"),
+    (libc::SIGCONT, "SIGCONT", "continued"),
+    (libc::SIGSTOP, "SIGSTOP", "stopped"),
+    (libc::SIGTSTP, "SIGTSTP", "terminal stop"),
+    (libc::SIGTTIN, "SIGTTIN", "background read from tty"),
+    (libc::SIGTTOU, "SIGTTOU", "background write to tty"),
+    (libc::SIGURG, "SIGURG", "urgent I/O condition"),
+    (libc::SIGXCPU, "SIGXCPU", "CPU time limit exceeded"),
+    (libc::SIGXFSZ, "SIGXFSZ", "file size limit exceeded"),
+    (libc::SIGVTALRM, "SIGVTALRM", "virtual timer expired"),
+    (libc::SIGPROF, "SIGPROF", "profiling timer expired"),
+    (libc::SIGWINCH, "SIGWINCH", "window changed"),
+    (libc::SIGIO, "SIGIO", "I/O possible"),
+    (libc::SIGSYS, "SIGSYS", "bad system call"),
+];
+
+// maps a signal to a short description and the name of its report folder,
+// real time and other unnamed signals are named by their number
+pub fn describe_signal(sig: i32) -> (&'static str, String) {
+    if let Some((_, name, desc)) = SIGNALS.iter().find(|(num, _, _)| *num == sig) {
+        return (desc, (*name).to_string());
+    }
+
+    if (libc::SIGRTMIN()..=libc::SIGRTMAX()).contains(&sig) {
+        return (
+            "real time signal",
+            format!("SIGRTMIN+{}", sig - libc::SIGRTMIN()),
+        );
+    }
+    ("unknown signal", format!("SIG{sig}"))
+}
@@ -0,0 +1,277 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt::Write as _,
+    io::{BufRead, BufReader, Write},
+    path::Path,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{Receiver, RecvTimeoutError, channel},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Result, anyhow};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+// A mutator running as a separate process, so mutators can be written in any
+// language. anomie spawns it once and talks to it over its stdin and stdout,
+// one JSON object per line. Bytes are sent as hex strings.
+//
+// {"op": "seeds"}
+//   -> {"seeds": [{"name": "connect", "data": "100c0004..."}]}
+// {"op": "mutate", "data": "100c0004...", "rng_seed": 42, "max_len": 1048576}
+//   -> {"data": "100c0104...", "description": "set the clean session flag"}
+//
+// A mutation replies with "data": null when it can't be applied, and any
+// request can be answered with {"error": "..."}. The same data and rng_seed
+// must give the same result, the replay command relies on it. The seeds are
+// only asked for when fuzzing with `--fuzz-type external`, a mutator can
+// reply with an empty list to use random seeds.
+//
+// A mutator that exits, hangs or replies with something that isn't JSON is
+// restarted and asked again once. One whose requests keep failing is disabled
+// for the rest of the run.
+#[derive(Debug)]
+pub struct ExternalMutator {
+    pub name: String, // `external::` followed by the script's file stem
+    pub command: String,
+    process: RefCell<Option<MutatorProcess>>,
+    failures: Cell<usize>, // failed requests in a row
+}
+
+// how long a reply can take before the mutator counts as hung
+const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+// a hung mutator costs two timeouts per request, so give up on it quickly
+const MAX_CONSECUTIVE_FAILURES: usize = 3;
+
+// stdout is read on its own thread so replies can time out
+#[derive(Debug)]
+struct MutatorProcess {
+    child: Child,
+    stdin: ChildStdin,
+    replies: Receiver<String>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum Request {
+    Seeds,
+    Mutate {
+        data: String,
+        rng_seed: u64,
+        max_len: usize,
+    },
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    error: Option<String>,
+    #[serde(default)]
+    seeds: Vec<ExternalSeed>,
+    #[serde(default)]
+    data: Option<String>,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ExternalSeed {
+    name: String,
+    data: String,
+}
+
+impl ExternalMutator {
+    // the process is started on the first request. it's named after the
+    // first argument that is a file, eg. the script of `python3 mut.py -v`,
+    // or after the program when there is none
+    pub fn new(command: &str) -> Result<Self> {
+        let mut words = command.split_whitespace();
+        let program = words
+            .next()
+            .ok_or_else(|| anyhow!("the external mutator command is empty"))?;
+        let script = words
+            .find(|word| !word.starts_with('-') && Path::new(word).is_file())
+            .unwrap_or(program);
+        let stem = Path::new(script)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        Ok(Self {
+            name: format!("external::{stem}"),
+            command: command.to_string(),
+            process: RefCell::new(None),
+            failures: Cell::new(0),
+        })
+    }
+
+    // the seeds the mutator provides, as (name, content) pairs
+    pub fn seeds(&self) -> Result<Vec<(String, Vec<u8>)>> {
+        self.request(&Request::Seeds)?
+            .seeds
+            .into_iter()
+            .map(|seed| Ok((seed.name, from_hex(&seed.data)?)))
+            .collect()
+    }
+
+    // the mutated input and its description, None if the mutation didn't apply
+    pub fn mutate(
+        &self,
+        input: &[u8],
+        rng_seed: u64,
+        max_len: usize,
+    ) -> Result<Option<(Vec<u8>, String)>> {
+        if self.disabled() {
+            return Ok(None);
+        }
+        let response = self.request(&Request::Mutate {
+            data: to_hex(input),
+            rng_seed,
+            max_len,
+        })?;
+        let Some(data) = response.data else {
+            return Ok(None);
+        };
+        let mut mutated = from_hex(&data)?;
+        mutated.truncate(max_len);
+        Ok(Some((mutated, response.description.unwrap_or_default())))
+    }
+
+    fn disabled(&self) -> bool {
+        self.failures.get() >= MAX_CONSECUTIVE_FAILURES
+    }
+
+    fn request(&self, request: &Request) -> Result<Response> {
+        let result = self.try_request(request);
+        if result.is_ok() {
+            self.failures.set(0);
+        } else {
+            self.failures.set(self.failures.get() + 1);
+            if self.disabled() {
+                warn!(
+                    "External mutator {} failed {MAX_CONSECUTIVE_FAILURES} times in a row, disabling it",
+                    self.name
+                );
+                self.stop();
+            }
+        }
+        result
+    }
+
+    fn try_request(&self, request: &Request) -> Result<Response> {
+        let line = serde_json::to_string(request)?;
+        let response = match self.exchange(&line) {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("External mutator {} failed: {e}, restarting it", self.name);
+                self.stop();
+                self.exchange(&line).inspect_err(|_| self.stop())?
+            }
+        };
+        match response.error {
+            Some(error) => Err(anyhow!("external mutator {} failed: {error}", self.name)),
+            None => Ok(response),
+        }
+    }
+
+    // sends a request and reads its reply, starting the process if needed
+    fn exchange(&self, line: &str) -> Result<Response> {
+        let mut process = self.process.borrow_mut();
+        if process.is_none() {
+            *process = Some(self.spawn()?);
+        }
+        let process = process
+            .as_mut()
+            .ok_or_else(|| anyhow!("external mutator {} isn't running", self.name))?;
+
+        writeln!(process.stdin, "{line}")
+            .and_then(|()| process.stdin.flush())
+            .map_err(|e| anyhow!("can't write to external mutator {}: {e}", self.name))?;
+
+        let reply = match process.replies.recv_timeout(REPLY_TIMEOUT) {
+            Ok(reply) => reply,
+            Err(RecvTimeoutError::Timeout) => {
+                return Err(anyhow!(
+                    "external mutator {} didn't reply in {}s",
+                    self.name,
+                    REPLY_TIMEOUT.as_secs()
+                ));
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(anyhow!("external mutator {} exited", self.name));
+            }
+        };
+
+        serde_json::from_str(&reply)
+            .map_err(|e| anyhow!("invalid reply from external mutator {}: {e}", self.name))
+    }
+
+    // kills the process, the next request starts a new one
+    fn stop(&self) {
+        if let Some(mut process) = self.process.borrow_mut().take() {
+            let _ = process.child.kill();
+            let _ = process.child.wait();
+        }
+    }
+
+    fn spawn(&self) -> Result<MutatorProcess> {
+        debug!("Starting external mutator {}", self.command);
+        let mut words = self.command.split_whitespace();
+        let program = words.next().unwrap_or_default();
+        let mut child = Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| anyhow!("can't start external mutator {}: {e}", self.command))?;
+
+        let (Some(stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            return Err(anyhow!("external mutator {} has no stdio", self.command));
+        };
+
+        // the thread ends once the process closes its stdout
+        let (sender, replies) = channel();
+        thread::spawn(move || {
+            let mut stdout = BufReader::new(stdout);
+            let mut reply = String::new();
+            while matches!(stdout.read_line(&mut reply), Ok(read) if read > 0) {
+                if sender.send(std::mem::take(&mut reply)).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(MutatorProcess {
+            child,
+            stdin,
+            replies,
+        })
+    }
+}
+
+impl Drop for ExternalMutator {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut s = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(&mut s, "{byte:02x}");
+    }
+    s
+}
+
+fn from_hex(hex: &str) -> Result<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return Err(anyhow!("odd number of hex digits"));
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| {
+            hex.get(i..i + 2)
+                .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+                .ok_or_else(|| anyhow!("invalid hex byte at {i}"))
+        })
+        .collect()
+}
@@ -0,0 +1,46 @@
+use std::{fs, path::Path, rc::Rc};
+
+use anyhow::Result;
+use rand::{Rng, rngs::SmallRng};
+
+use super::template::FileFormat;
+use crate::mutations::{MutationContext, MutatorRegistry};
+
+// Inputs only an external mutator knows the structure of, eg. a network
+// protocol. The external mutators are the only mutators, and they provide the
+// seeds too.
+pub struct External;
+#[derive(Clone)]
+pub struct ExternalModel {
+    pub bytes: Vec<u8>,
+}
+
+impl FileFormat for External {
+    type Model = ExternalModel;
+    const EXT: &str = "bin";
+
+    fn parse(input: &[u8]) -> Result<Self::Model> {
+        Ok(ExternalModel {
+            bytes: input.to_vec(),
+        })
+    }
+
+    fn generate(model: Self::Model) -> Result<Vec<u8>> {
+        Ok(model.bytes)
+    }
+
+    // used when the external mutator has no seeds of its own
+    fn generate_corpus(rng: &mut SmallRng, corpus_dir: &Path) -> Result<()> {
+        for i in 0..20 {
+            let length = rng.random_range(1..256);
+            let content: Vec<u8> = (0..length).map(|_| rng.random()).collect();
+            fs::write(corpus_dir.join(format!("{i}.bin")), content)?;
+        }
+        Ok(())
+    }
+
+    // the external mutators are added to every format's registry
+    fn mutators(_context: &Rc<MutationContext>) -> MutatorRegistry<Self::Model> {
+        MutatorRegistry::new()
+    }
+}
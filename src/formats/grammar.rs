@@ -0,0 +1,85 @@
+use std::{path::Path, rc::Rc};
+
+use anyhow::{Result, anyhow};
+use rand::rngs::SmallRng;
+
+use super::template::FileFormat;
+use crate::{
+    grammar::Node,
+    mutations::{MutationContext, MutatorRegistry, grammar as grammar_mutations},
+};
+
+// Text inputs described by a user supplied grammar (see src/grammar.rs), eg.
+// config files or query languages. Mutators work on the derivation tree
+// instead of the bytes, so inputs stay in the grammar's language.
+pub struct GrammarInput;
+#[derive(Clone)]
+pub struct GrammarModel {
+    pub bytes: Vec<u8>,
+    // parsed from the bytes by the first grammar mutation, the input is
+    // generated from it from then on
+    pub derivation: Option<Node>,
+}
+
+impl FileFormat for GrammarInput {
+    type Model = GrammarModel;
+    const EXT: &str = "txt";
+
+    fn parse(input: &[u8]) -> Result<Self::Model> {
+        Ok(GrammarModel {
+            bytes: input.to_vec(),
+            derivation: None,
+        })
+    }
+
+    fn generate(model: Self::Model) -> Result<Vec<u8>> {
+        Ok(match model.derivation {
+            Some(derivation) => derivation.unparse(),
+            None => model.bytes,
+        })
+    }
+
+    // the corpus is generated from the grammar passed with --grammar
+    fn generate_corpus(_rng: &mut SmallRng, _corpus_dir: &Path) -> Result<()> {
+        Err(anyhow!("the grammar fuzz type needs a --grammar"))
+    }
+
+    fn mutators(context: &Rc<MutationContext>) -> MutatorRegistry<Self::Model> {
+        let Some(grammar) = &context.grammar else {
+            return MutatorRegistry::new();
+        };
+        let replace_grammar = Rc::clone(grammar);
+        let recursion_grammar = Rc::clone(grammar);
+        let splice_context = Rc::clone(context);
+        MutatorRegistry::new()
+            .register(
+                "grammar::replace_subtree",
+                4,
+                move |rng: &mut SmallRng, model: &mut GrammarModel| {
+                    Ok(grammar_mutations::replace_subtree(
+                        rng,
+                        model,
+                        &replace_grammar,
+                    ))
+                },
+            )
+            .register(
+                "grammar::expand_recursion",
+                2,
+                move |rng: &mut SmallRng, model: &mut GrammarModel| {
+                    Ok(grammar_mutations::expand_recursion(
+                        rng,
+                        model,
+                        &recursion_grammar,
+                    ))
+                },
+            )
+            .register(
+                "grammar::splice",
+                2,
+                move |rng: &mut SmallRng, model: &mut GrammarModel| {
+                    Ok(grammar_mutations::splice(rng, model, &splice_context))
+                },
+            )
+    }
+}
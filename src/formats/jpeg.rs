@@ -1,8 +1,8 @@
 use super::template::FileFormat;
-use crate::mutate::mutate_bytes;
+use crate::mutations::{MutationContext, MutatorRegistry, jpeg as jpeg_mutations};
 use anyhow::Result;
 use rand::{Rng, rngs::SmallRng};
-use std::{collections::HashSet, path::Path};
+use std::{path::Path, rc::Rc};
 
 pub struct Jpeg;
 #[derive(Clone)]
@@ -25,6 +25,28 @@ pub enum JpegSegment {
 impl FileFormat for Jpeg {
     type Model = JpegModel;
     const EXT: &str = "jpg";
+    const DICTIONARY: &'static [&'static [u8]] = &[
+        b"\xFF\xD8",
+        b"\xFF\xD9",
+        b"\xFF\xC0",
+        b"\xFF\xC2",
+        b"\xFF\xC4",
+        b"\xFF\xDB",
+        b"\xFF\xDA",
+        b"\xFF\xDD",
+        b"\xFF\xE0",
+        b"\xFF\xE1",
+        b"\xFF\xE2",
+        b"\xFF\xEE",
+        b"JFIF\0",
+        b"JFXX\0",
+        b"Exif\0\0",
+        b"ICC_PROFILE\0",
+        b"http://ns.adobe.com/xap/1.0/\0",
+        b"Adobe",
+        b"MM\0*",
+        b"II*\0",
+    ];
 
     #[allow(clippy::too_many_lines, unused_assignments)]
     fn parse(input: &[u8]) -> Result<Self::Model> {
@@ -267,150 +289,123 @@ impl FileFormat for Jpeg {
         Ok(())
     }
 
-    #[allow(
-        clippy::too_many_lines,
-        clippy::cast_possible_truncation,
-        clippy::cast_sign_loss,
-        clippy::cast_precision_loss
-    )]
-    fn mutate(rng: &mut SmallRng, model: &mut Self::Model) -> Result<String> {
-        let mut mutation_desc = String::new();
-        if model.segments.is_empty() {
-            return Ok(String::new());
-        }
-
-        match rng.random_range(0..=9) {
-            0 => {
-                let cut = rng.random_range(0..model.segments.len());
-                model.segments.truncate(cut);
-                mutation_desc = "truncated in half".to_string();
-            }
-            1 => {
-                // remove EOI - last 2 bytes are a flag that represent the end
-                // of the jpeg
-                model.eoi.clear();
-                mutation_desc = "removed EOI".to_string();
-            }
-            2 => {
-                // corrupt SOI - replace the traditional jpeg start flag with a random byte
-                let rand_byte = rng.random::<u8>();
-                model.soi[1] = rand_byte;
-                mutation_desc =
-                    format!("corrupted SOI by inserting {rand_byte} into the second index");
-            }
-            3 => {
-                // corrupt SOF - change the expected width/height of the file
-                // xFF xC0 corresponds to baseline
-                // xFF xC2 corresponds to progressive
-                for seg in &mut model.segments {
-                    if let JpegSegment::Sof(data) = seg {
-                        if !data.is_empty() {
-                            data[5] = rng.random::<u8>();
-                            data[6] = rng.random::<u8>();
-                            data[7] = rng.random::<u8>();
-                            data[8] = rng.random::<u8>();
-                        }
-                    }
-                }
-                mutation_desc = "overwriting the expected width/height".to_string();
-            }
-            4 => {
-                // byteflip non-header data, flip at most 2% of all non-header bytes
-                let mut bytes = Self::generate(model.clone())?;
-                let mutation_rate = rng.random_range(0.001..0.02);
-                let total_byteflip_mutations = (bytes.len() as f64 * mutation_rate).ceil() as usize;
-
-                // first collect all header indicies
-                let mut header_indices = HashSet::new();
-                for i in 0..bytes.len() - 1 {
-                    if bytes[i] == 0xFF && bytes[i + 1] != 0x00 {
-                        header_indices.insert(i);
-                        header_indices.insert(i + 1);
-                    }
-                }
-
-                for _ in 0..total_byteflip_mutations {
-                    let mut index = rng.random_range(0..bytes.len() - 2);
-                    while header_indices.contains(&index) {
-                        index = rng.random_range(0..bytes.len() - 2);
-                    }
-                    bytes[index] ^= 1 << rng.random_range(0..8);
-                }
-
-                let new_model = Self::parse(&bytes)?;
-                *model = new_model;
-                mutation_desc = format!(
-                    "byteflipping {:.2}% of nonheader data",
-                    mutation_rate * 100.0,
-                );
-            }
-            5 => {
-                // add trailing garbage bytes at end
-                let tail_length = rng.random_range(0..10_000);
-                for _ in 0..tail_length {
-                    model.eoi.push(rng.random::<u8>());
-                }
-                mutation_desc =
-                    format!("adding {tail_length} random bytes at the end of the file data",);
-            }
-            6 => {
-                // overwrite segment lengths, the two bytes after the segment header indicate the segment length
-                let random_seg_idx = rng.random_range(..model.segments.len());
-                let segment = &mut model.segments[random_seg_idx];
-
-                let (data, segment_name) = match segment {
-                    JpegSegment::App(v) => (v, "APP"),
-                    JpegSegment::Dqt(v) => (v, "DQT"),
-                    JpegSegment::Sof(v) => (v, "SOF"),
-                    JpegSegment::Dht(v) => (v, "DHT"),
-                    JpegSegment::Sos(v) => (v, "SOS"),
-                    JpegSegment::Dat(v) => (v, "DAT"),
-                };
-
-                // generate two bytes from the same rng call
-                let [high_byte, low_byte] = rng.random::<u16>().to_be_bytes();
-                data[2] = high_byte;
-                data[3] = low_byte;
-
-                mutation_desc = format!(
-                    "overwriting the segment length of the {segment_name} segment to be {:?}",
-                    (u16::from(high_byte) >> 8) + u16::from(low_byte),
-                );
-            }
-            7 => {
-                // rearrange the segments
-                let mut first = usize::MAX;
-                let mut second = usize::MAX;
-                while first == second {
-                    first = rng.random_range(0..model.segments.len());
-                    second = rng.random_range(0..model.segments.len());
-                }
-                let temp = model.segments[first].clone();
-                model.segments[first] = model.segments[second].clone();
-                model.segments[second] = temp;
-                mutation_desc =
-                    format!("swapping the two segments at positions {first} and {second}");
-            }
-            8 => {
-                // alter dht tables
-                for seg in &mut model.segments {
-                    if let JpegSegment::Dht(data) = seg {
-                        let byte_mutation_desc = mutate_bytes(rng, &mut data[5..]);
-                        mutation_desc = format!("mutating one of the dhts: {byte_mutation_desc}");
-                    }
-                }
-            }
-            9 => {
-                // alter dqt tables
-                for seg in &mut model.segments {
-                    if let JpegSegment::Dqt(data) = seg {
-                        let byte_mutation_desc = mutate_bytes(rng, &mut data[5..]);
-                        mutation_desc = format!("mutating one of the dqts: {byte_mutation_desc}");
-                    }
-                }
-            }
-            _ => unreachable!(),
-        }
-        Ok(mutation_desc)
+    fn mutators(context: &Rc<MutationContext>) -> MutatorRegistry<Self::Model> {
+        let segment_context = Rc::clone(context);
+        let data_context = Rc::clone(context);
+        let tables_context = Rc::clone(context);
+        let transplant_context = Rc::clone(context);
+        MutatorRegistry::new()
+            .register(
+                "jpeg::truncate",
+                1,
+                |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::truncate(model, rng))
+                },
+            )
+            .register(
+                "jpeg::remove_eoi",
+                1,
+                |_: &mut SmallRng, model: &mut JpegModel| Ok(jpeg_mutations::remove_eoi(model)),
+            )
+            .register(
+                "jpeg::corrupt_soi",
+                1,
+                |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::corrupt_soi(model, rng))
+                },
+            )
+            .register(
+                "jpeg::change_dims",
+                1,
+                |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::change_dims(model, rng))
+                },
+            )
+            .register(
+                "jpeg::flip_data_bits",
+                1,
+                |rng: &mut SmallRng, model: &mut JpegModel| {
+                    jpeg_mutations::flip_data_bits(model, rng)
+                },
+            )
+            .register(
+                "jpeg::append_garbage",
+                1,
+                |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::append_garbage(model, rng))
+                },
+            )
+            .register(
+                "jpeg::change_segment_length",
+                1,
+                |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::change_segment_length(model, rng))
+                },
+            )
+            .register(
+                "jpeg::swap_segments",
+                1,
+                |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::swap_segments(model, rng))
+                },
+            )
+            .register(
+                "jpeg::mutate_dht",
+                1,
+                |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::mutate_dht(model, rng))
+                },
+            )
+            .register(
+                "jpeg::mutate_dqt",
+                1,
+                |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::mutate_dqt(model, rng))
+                },
+            )
+            .register(
+                "jpeg::insert_dict_segment",
+                2,
+                move |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::insert_dict_segment(
+                        model,
+                        rng,
+                        &segment_context.dictionary,
+                    ))
+                },
+            )
+            .register(
+                "jpeg::dict_segment_data",
+                2,
+                move |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::dict_segment_data(
+                        model,
+                        rng,
+                        &data_context.dictionary,
+                    ))
+                },
+            )
+            .register(
+                "jpeg::swap_tables",
+                2,
+                move |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::swap_tables(
+                        model,
+                        rng,
+                        &tables_context.corpus,
+                    ))
+                },
+            )
+            .register(
+                "jpeg::transplant_segment",
+                2,
+                move |rng: &mut SmallRng, model: &mut JpegModel| {
+                    Ok(jpeg_mutations::transplant_segment(
+                        model,
+                        rng,
+                        &transplant_context.corpus,
+                    ))
+                },
+            )
     }
 }
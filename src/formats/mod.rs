@@ -1,3 +1,5 @@
+pub mod external;
+pub mod grammar;
 pub mod jpeg;
 pub mod png;
 pub mod string;
@@ -1,20 +1,22 @@
 use std::{
     io::{Cursor, Read},
     path::PathBuf,
+    rc::Rc,
 };
 
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use byteorder::{BigEndian, ReadBytesExt};
 use crc32fast::Hasher;
 use log::{debug, warn};
-use rand::Rng;
+use rand::rngs::SmallRng;
 
-use crate::mutations::png as png_mutations;
+use crate::mutations::{MutationContext, MutatorRegistry, png as png_mutations};
 
 use super::template::FileFormat;
 
 pub struct Png;
 
+#[derive(Clone)]
 pub struct PngModel {
     pub signature: [u8; 8],
     pub chunks: Vec<Chunk>,
@@ -22,7 +24,7 @@ pub struct PngModel {
 
 const PNG_SIGNATURE: [u8; 8] = [0x89, 0x50, 0x4E, 0x47, 0x0D, 0x0A, 0x1A, 0x0A];
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Chunk {
     Ihdr(Ihdr, ChunkCrc),
     Idat(Vec<u8>, ChunkCrc),
@@ -30,7 +32,7 @@ pub enum Chunk {
     Ancillary(RawChunk),
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Ihdr {
     pub width: u32,
     pub height: u32,
@@ -41,12 +43,12 @@ pub struct Ihdr {
     pub interlace_method: u8,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ChunkCrc {
     pub crc: u32,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct RawChunk {
     pub length: u32,
     pub chunk_type: [u8; 4],
@@ -61,6 +63,11 @@ fn parse_chunk(cursor: &mut Cursor<&[u8]>) -> Result<RawChunk> {
     let mut chunk_type = [0u8; 4];
     cursor.read_exact(&mut chunk_type)?;
 
+    // external mutators can hand back anything, don't trust the length
+    let remaining = cursor.get_ref().len() as u64 - cursor.position();
+    if u64::from(length) > remaining {
+        return Err(anyhow!("chunk length {length} is past the end of the file"));
+    }
     let mut data = vec![0; length as usize];
     cursor.read_exact(&mut data)?;
 
@@ -75,6 +82,9 @@ fn parse_chunk(cursor: &mut Cursor<&[u8]>) -> Result<RawChunk> {
 }
 
 fn bytes_to_ihdr(data: &[u8]) -> Result<Ihdr> {
+    if data.len() < 13 {
+        return Err(anyhow!("IHDR is {} bytes long", data.len()));
+    }
     Ok(Ihdr {
         width: u32::from_be_bytes(data[0..4].try_into()?),
         height: u32::from_be_bytes(data[4..8].try_into()?),
@@ -149,7 +159,7 @@ fn write_chunk(byte_stream: &mut Vec<u8>, ty: &[u8], data: &[u8], opt_crc: Optio
     }
 }
 
-fn png_crc(ty: &[u8], data: &[u8]) -> u32 {
+pub fn png_crc(ty: &[u8], data: &[u8]) -> u32 {
     let mut hasher = Hasher::new();
     hasher.update(ty);
     hasher.update(data);
@@ -185,6 +195,31 @@ fn normalize_png(model: &mut PngModel) {
 impl FileFormat for Png {
     type Model = PngModel;
     const EXT: &'static str = "png";
+    const DICTIONARY: &'static [&'static [u8]] = &[
+        &PNG_SIGNATURE,
+        b"IHDR",
+        b"PLTE",
+        b"IDAT",
+        b"IEND",
+        b"tRNS",
+        b"cHRM",
+        b"gAMA",
+        b"iCCP",
+        b"sBIT",
+        b"sRGB",
+        b"tEXt",
+        b"zTXt",
+        b"iTXt",
+        b"bKGD",
+        b"hIST",
+        b"pHYs",
+        b"sPLT",
+        b"tIME",
+        b"eXIf",
+        b"acTL",
+        b"fcTL",
+        b"fdAT",
+    ];
 
     fn parse(input: &[u8]) -> Result<Self::Model> {
         let mut cursor = Cursor::new(input);
@@ -303,28 +338,118 @@ impl FileFormat for Png {
         Ok(())
     }
 
-    fn mutate(rng: &mut rand::prelude::SmallRng, model: &mut Self::Model) -> Result<String> {
-        if model.chunks.is_empty() {
-            return Ok(String::new());
-        }
-
-        let mutation_desc = match rng.random_range(0..9) {
-            0 => png_mutations::remove_ihdr(model),
-            1 => png_mutations::remove_idat(model),
-            2 => png_mutations::remove_iend(model),
-            3 => png_mutations::change_png_dims(model, rng),
-            4 => png_mutations::change_depth(model, rng),
-            5 => png_mutations::change_color_type(model, rng),
-            6 => png_mutations::change_compression_method(model, rng),
-            7 => png_mutations::change_filter_method(model, rng),
-            8 => png_mutations::change_interlace_method(model, rng),
-            9 => png_mutations::xor_crc(model, rng),
-            10 => png_mutations::zero_crc(model, rng),
-            _ => {
-                unreachable!()
-            }
-        };
-
-        Ok(mutation_desc)
+    fn mutators(context: &Rc<MutationContext>) -> MutatorRegistry<Self::Model> {
+        let chunk_context = Rc::clone(context);
+        let data_context = Rc::clone(context);
+        let transplant_context = Rc::clone(context);
+        let ihdr_context = Rc::clone(context);
+        MutatorRegistry::new()
+            .register(
+                "png::remove_ihdr",
+                1,
+                |_: &mut SmallRng, model: &mut PngModel| Ok(png_mutations::remove_ihdr(model)),
+            )
+            .register(
+                "png::remove_idat",
+                1,
+                |_: &mut SmallRng, model: &mut PngModel| Ok(png_mutations::remove_idat(model)),
+            )
+            .register(
+                "png::remove_iend",
+                1,
+                |_: &mut SmallRng, model: &mut PngModel| Ok(png_mutations::remove_iend(model)),
+            )
+            .register(
+                "png::change_dims",
+                1,
+                |rng: &mut SmallRng, model: &mut PngModel| {
+                    Ok(png_mutations::change_png_dims(model, rng))
+                },
+            )
+            .register(
+                "png::change_depth",
+                1,
+                |rng: &mut SmallRng, model: &mut PngModel| {
+                    Ok(png_mutations::change_depth(model, rng))
+                },
+            )
+            .register(
+                "png::change_color_type",
+                1,
+                |rng: &mut SmallRng, model: &mut PngModel| {
+                    Ok(png_mutations::change_color_type(model, rng))
+                },
+            )
+            .register(
+                "png::change_compression_method",
+                1,
+                |rng: &mut SmallRng, model: &mut PngModel| {
+                    Ok(png_mutations::change_compression_method(model, rng))
+                },
+            )
+            .register(
+                "png::change_filter_method",
+                1,
+                |rng: &mut SmallRng, model: &mut PngModel| {
+                    Ok(png_mutations::change_filter_method(model, rng))
+                },
+            )
+            .register(
+                "png::change_interlace_method",
+                1,
+                |rng: &mut SmallRng, model: &mut PngModel| {
+                    Ok(png_mutations::change_interlace_method(model, rng))
+                },
+            )
+            .register(
+                "png::xor_crc",
+                1,
+                |rng: &mut SmallRng, model: &mut PngModel| Ok(png_mutations::xor_crc(model, rng)),
+            )
+            .register(
+                "png::zero_crc",
+                1,
+                |rng: &mut SmallRng, model: &mut PngModel| Ok(png_mutations::zero_crc(model, rng)),
+            )
+            .register(
+                "png::insert_dict_chunk",
+                2,
+                move |rng: &mut SmallRng, model: &mut PngModel| {
+                    Ok(png_mutations::insert_dict_chunk(
+                        model,
+                        rng,
+                        &chunk_context.dictionary,
+                    ))
+                },
+            )
+            .register(
+                "png::dict_chunk_data",
+                2,
+                move |rng: &mut SmallRng, model: &mut PngModel| {
+                    Ok(png_mutations::dict_chunk_data(
+                        model,
+                        rng,
+                        &data_context.dictionary,
+                    ))
+                },
+            )
+            .register(
+                "png::transplant_chunk",
+                2,
+                move |rng: &mut SmallRng, model: &mut PngModel| {
+                    Ok(png_mutations::transplant_chunk(
+                        model,
+                        rng,
+                        &transplant_context.corpus,
+                    ))
+                },
+            )
+            .register(
+                "png::swap_ihdr",
+                1,
+                move |rng: &mut SmallRng, model: &mut PngModel| {
+                    Ok(png_mutations::swap_ihdr(model, rng, &ihdr_context.corpus))
+                },
+            )
     }
 }
@@ -1,13 +1,16 @@
-use std::{fs, io::Write, path::Path};
+use std::{fs, io::Write, path::Path, rc::Rc};
 
 use anyhow::Result;
-use rand::rngs::SmallRng;
 
-use crate::mutate::mutate_bytes;
+use crate::{
+    mutate::byte_mutators,
+    mutations::{MutationContext, MutatorRegistry},
+};
 
 use super::template::FileFormat;
 
 pub struct FuzzString;
+#[derive(Clone)]
 pub struct FuzzStringModel {
     pub filename: Vec<u8>,
 }
@@ -46,7 +49,7 @@ impl FileFormat for FuzzString {
         Ok(())
     }
 
-    fn mutate(rng: &mut SmallRng, model: &mut Self::Model) -> Result<String> {
-        Ok(mutate_bytes(rng, &mut model.filename))
+    fn mutators(context: &Rc<MutationContext>) -> MutatorRegistry<Self::Model> {
+        byte_mutators(context, |model: &mut FuzzStringModel| &mut model.filename)
     }
 }
@@ -1,11 +1,16 @@
-use std::path::Path;
+use std::{path::Path, rc::Rc};
 
 use anyhow::Result;
 use rand::rngs::SmallRng;
 
+use crate::mutations::{MutationContext, MutatorRegistry};
+
 pub trait FileFormat {
-    type Model;
+    // cloned to hand the generated bytes to external mutators
+    type Model: Clone + 'static;
     const EXT: &'static str;
+    // tokens the dictionary mutators always have, on top of the user's
+    const DICTIONARY: &'static [&'static [u8]] = &[];
 
     // parse an array of bytes into a file
     fn parse(input: &[u8]) -> Result<Self::Model>;
@@ -16,8 +21,9 @@ pub trait FileFormat {
     // generate a corpus
     fn generate_corpus(rng: &mut SmallRng, corpus_dir: &Path) -> Result<()>;
 
-    // apply mutations to a model
-    fn mutate(rng: &mut SmallRng, model: &mut Self::Model) -> Result<String>;
+    // every mutator that can be applied to a model, with its default weight.
+    // `context` holds the dictionary tokens and the corpus to splice with
+    fn mutators(context: &Rc<MutationContext>) -> MutatorRegistry<Self::Model>;
 
     // saves the file, returns the path to the saved file as a string
     // fn save_file(model: Self::Model) -> Result<String>;
@@ -1,10 +1,14 @@
 use super::template::FileFormat;
-use crate::mutate::mutate_bytes;
+use crate::{
+    mutate::byte_mutators,
+    mutations::{MutationContext, MutatorRegistry},
+};
 use anyhow::Result;
 use rand::{Rng, rngs::SmallRng};
-use std::{fs, io::Write, path::Path};
+use std::{fs, io::Write, path::Path, rc::Rc};
 
 pub struct Txt;
+#[derive(Clone)]
 pub struct TxtModel {
     pub bytes: Vec<u8>,
 }
@@ -39,7 +43,7 @@ impl FileFormat for Txt {
         Ok(())
     }
 
-    fn mutate(rng: &mut SmallRng, model: &mut Self::Model) -> Result<String> {
-        Ok(mutate_bytes(rng, &mut model.bytes))
+    fn mutators(context: &Rc<MutationContext>) -> MutatorRegistry<Self::Model> {
+        byte_mutators(context, |model: &mut TxtModel| &mut model.bytes)
     }
 }
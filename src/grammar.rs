@@ -0,0 +1,798 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    fs,
+    hash::{DefaultHasher, Hash, Hasher},
+    path::Path,
+    sync::LazyLock,
+};
+
+use anyhow::{Result, anyhow};
+use rand::{rngs::SmallRng, seq::IndexedRandom};
+use regex::Regex;
+
+use crate::dictionary::{escape, unescape};
+
+// where the grammar a campaign used is saved in its report
+pub const GRAMMAR_FILE: &str = "grammar.bnf";
+
+// inputs generated for the corpus
+const CORPUS_SIZE: usize = 20;
+// depth random derivations aim for, deeper nonterminals only expand into
+// their shallowest alternatives
+pub const GENERATE_DEPTH: usize = 12;
+// derivations are kept small enough to parse back in reasonable time
+pub const MAX_DERIVATION_LEN: usize = 16 * 1024;
+pub const MAX_DERIVATION_DEPTH: usize = 256;
+
+static NONTERMINAL_REGEX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<[^<> ]*>").unwrap());
+
+// A context-free grammar the grammar fuzz type generates and mutates inputs
+// with. Either BNF, where the first rule is the start symbol
+//
+// # comment
+// <expr> ::= <term> "+" <expr> | <term>
+// <term> ::= "(" <expr> ")"
+//          | "1" | "\x00"
+//
+// or a JSON object mapping nonterminals to their expansions, starting from
+// `<start>`, eg. {"<start>": ["<expr>"], "<expr>": ["<term> + <expr>", "<term>"]}.
+// Nonterminals are indexed in the order they are defined.
+#[derive(Debug)]
+pub struct Grammar {
+    names: Vec<String>,
+    rules: Vec<Vec<Vec<Symbol>>>, // the alternatives of each nonterminal
+    depths: Vec<Vec<usize>>,      // shallowest derivation of each alternative
+    nullable: Vec<bool>,
+    start: usize,
+    parsed: RefCell<HashMap<u64, Option<Node>>>, // derivations of inputs by their hash
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Symbol {
+    Terminal(Vec<u8>),
+    NonTerminal(usize),
+}
+
+// A node of a derivation tree, the terminals are kept in the tree so an
+// input can be generated from it without the grammar
+#[derive(Debug, Clone)]
+pub enum Node {
+    Terminal(Vec<u8>),
+    NonTerminal { symbol: usize, children: Vec<Node> },
+}
+
+enum RawSymbol {
+    Terminal(Vec<u8>),
+    NonTerminal(String),
+}
+
+type RawRule = (String, Vec<Vec<RawSymbol>>);
+
+enum Token {
+    NonTerminal(String),
+    Terminal(Vec<u8>),
+    Define,
+    Alternative,
+}
+
+impl Grammar {
+    // json grammars are recognised by their extension
+    pub fn load(path: &str) -> Result<Self> {
+        let contents =
+            fs::read_to_string(path).map_err(|e| anyhow!("can't read grammar {path}: {e}"))?;
+        let rules = if path.ends_with(".json") {
+            parse_json(&contents)
+        } else {
+            parse_bnf(&contents)
+        };
+        rules
+            .and_then(Self::new)
+            .map_err(|e| anyhow!("invalid grammar {path}: {e}"))
+    }
+
+    fn new(raw_rules: Vec<RawRule>) -> Result<Self> {
+        // rules of a nonterminal that is defined twice are merged
+        let mut names: Vec<String> = Vec::new();
+        let mut raw_alternatives: Vec<Vec<Vec<RawSymbol>>> = Vec::new();
+        for (name, alternatives) in raw_rules {
+            match names.iter().position(|n| *n == name) {
+                Some(idx) => raw_alternatives[idx].extend(alternatives),
+                None => {
+                    names.push(name);
+                    raw_alternatives.push(alternatives);
+                }
+            }
+        }
+        if names.is_empty() {
+            return Err(anyhow!("no rules are defined"));
+        }
+
+        let mut rules = Vec::new();
+        for alternatives in raw_alternatives {
+            let mut resolved = Vec::new();
+            for alternative in alternatives {
+                let mut symbols = Vec::new();
+                for symbol in alternative {
+                    match symbol {
+                        RawSymbol::Terminal(t) if t.is_empty() => {}
+                        RawSymbol::Terminal(t) => symbols.push(Symbol::Terminal(t)),
+                        RawSymbol::NonTerminal(name) => {
+                            let idx = names
+                                .iter()
+                                .position(|n| *n == name)
+                                .ok_or_else(|| anyhow!("{name} is used but never defined"))?;
+                            symbols.push(Symbol::NonTerminal(idx));
+                        }
+                    }
+                }
+                resolved.push(symbols);
+            }
+            rules.push(resolved);
+        }
+
+        let depths = min_depths(&rules);
+        for (name, alternatives) in names.iter().zip(&depths) {
+            if alternatives.iter().all(|&depth| depth == usize::MAX) {
+                return Err(anyhow!("{name} never derives a finite input"));
+            }
+        }
+
+        let mut nullable = vec![false; rules.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (symbol, alternatives) in rules.iter().enumerate() {
+                if !nullable[symbol]
+                    && alternatives.iter().any(|alternative| {
+                        alternative
+                            .iter()
+                            .all(|s| matches!(s, Symbol::NonTerminal(n) if nullable[*n]))
+                    })
+                {
+                    nullable[symbol] = true;
+                    changed = true;
+                }
+            }
+        }
+
+        Ok(Self {
+            names,
+            rules,
+            depths,
+            nullable,
+            start: 0,
+            parsed: RefCell::new(HashMap::new()),
+        })
+    }
+
+    pub fn name(&self, symbol: usize) -> &str {
+        self.names.get(symbol).map_or("", String::as_str)
+    }
+
+    pub fn nonterminals(&self) -> usize {
+        self.names.len()
+    }
+
+    // writes the grammar as BNF, loading it back gives the same indices
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut s = String::new();
+        for (name, alternatives) in self.names.iter().zip(&self.rules) {
+            let alternatives: Vec<String> = alternatives
+                .iter()
+                .map(|alternative| {
+                    if alternative.is_empty() {
+                        return "\"\"".to_string();
+                    }
+                    let symbols: Vec<String> = alternative
+                        .iter()
+                        .map(|symbol| match symbol {
+                            Symbol::Terminal(t) => format!("\"{}\"", escape(t)),
+                            Symbol::NonTerminal(n) => self.names[*n].clone(),
+                        })
+                        .collect();
+                    symbols.join(" ")
+                })
+                .collect();
+            writeln!(&mut s, "{name} ::= {}", alternatives.join(" | "))?;
+        }
+        fs::write(path, s)?;
+        Ok(())
+    }
+
+    // a random derivation of `symbol` about `depth` levels deep
+    pub fn generate(&self, rng: &mut SmallRng, symbol: usize, depth: usize) -> Node {
+        let depths = &self.depths[symbol];
+        let fitting: Vec<usize> = (0..depths.len())
+            .filter(|&alternative| depths[alternative] <= depth)
+            .collect();
+        // the shallowest alternative always gets closer to the terminals
+        let alternative = match fitting.choose(rng) {
+            Some(&alternative) => alternative,
+            None => (0..depths.len())
+                .min_by_key(|&alternative| depths[alternative])
+                .unwrap_or_default(),
+        };
+
+        let children = self.rules[symbol][alternative]
+            .iter()
+            .map(|s| match s {
+                Symbol::Terminal(t) => Node::Terminal(t.clone()),
+                Symbol::NonTerminal(n) => self.generate(rng, *n, depth.saturating_sub(1)),
+            })
+            .collect();
+        Node::NonTerminal { symbol, children }
+    }
+
+    // writes distinct random derivations of the start symbol
+    pub fn generate_corpus(&self, rng: &mut SmallRng, corpus_dir: &Path, ext: &str) -> Result<()> {
+        let mut generated = HashSet::new();
+        for _ in 0..CORPUS_SIZE * 10 {
+            if generated.len() == CORPUS_SIZE {
+                break;
+            }
+            let node = self.generate(rng, self.start, GENERATE_DEPTH);
+            let content = node.unparse();
+            if content.len() <= MAX_DERIVATION_LEN && generated.insert(content.clone()) {
+                fs::write(
+                    corpus_dir.join(format!("{}.{ext}", generated.len() - 1)),
+                    content,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    // the derivation of an input, remembered so seeds are only parsed once
+    pub fn parse_cached(&self, input: &[u8]) -> Option<Node> {
+        let mut hasher = DefaultHasher::new();
+        input.hash(&mut hasher);
+        self.parsed
+            .borrow_mut()
+            .entry(hasher.finish())
+            .or_insert_with(|| self.parse(input))
+            .clone()
+    }
+
+    // Earley parser, None if the input isn't in the language. Ambiguous
+    // inputs always get the same one of their derivations.
+    pub fn parse(&self, input: &[u8]) -> Option<Node> {
+        if input.len() > MAX_DERIVATION_LEN {
+            return None;
+        }
+        let completed = self.recognize(input);
+        let mut builder = TreeBuilder {
+            grammar: self,
+            input,
+            completed: &completed,
+            active: HashSet::new(),
+            failed: HashSet::new(),
+        };
+        builder.build(self.start, 0, input.len(), 0)
+    }
+
+    // the derivation with the children of each nonterminal in brackets, eg.
+    // `<expr>[<term>["1"] "+" <expr>[<term>["2"]]]`. A string rather than
+    // nested json, deep derivations would go past serde_json's recursion limit
+    pub fn derivation(&self, node: &Node) -> String {
+        let mut s = String::new();
+        self.write_derivation(node, &mut s);
+        s
+    }
+
+    fn write_derivation(&self, node: &Node, s: &mut String) {
+        match node {
+            Node::Terminal(t) => {
+                s.push('"');
+                s.push_str(&escape(t));
+                s.push('"');
+            }
+            Node::NonTerminal { symbol, children } => {
+                s.push_str(self.name(*symbol));
+                s.push('[');
+                for (i, child) in children.iter().enumerate() {
+                    if i > 0 {
+                        s.push(' ');
+                    }
+                    self.write_derivation(child, s);
+                }
+                s.push(']');
+            }
+        }
+    }
+
+    // the end of every completed (symbol, alternative) by where it starts
+    fn recognize(&self, input: &[u8]) -> HashMap<(usize, usize), Vec<(usize, usize)>> {
+        let mut chart: Vec<Vec<Item>> = vec![Vec::new(); input.len() + 1];
+        let mut seen: Vec<HashSet<Item>> = vec![HashSet::new(); input.len() + 1];
+        let mut completed: HashMap<(usize, usize), Vec<(usize, usize)>> = HashMap::new();
+        let mut add = |chart: &mut Vec<Vec<Item>>, pos: usize, item: Item| {
+            if seen[pos].insert(item) {
+                chart[pos].push(item);
+            }
+        };
+
+        for alternative in 0..self.rules[self.start].len() {
+            add(
+                &mut chart,
+                0,
+                Item {
+                    symbol: self.start,
+                    alternative,
+                    dot: 0,
+                    origin: 0,
+                },
+            );
+        }
+
+        for pos in 0..=input.len() {
+            let mut i = 0;
+            while let Some(&item) = chart[pos].get(i) {
+                i += 1;
+                let advanced = Item {
+                    dot: item.dot + 1,
+                    ..item
+                };
+                match self.rules[item.symbol][item.alternative].get(item.dot) {
+                    None => {
+                        completed
+                            .entry((item.symbol, item.origin))
+                            .or_default()
+                            .push((item.alternative, pos));
+                        let parents: Vec<Item> = chart[item.origin]
+                            .iter()
+                            .filter(|parent| {
+                                self.rules[parent.symbol][parent.alternative].get(parent.dot)
+                                    == Some(&Symbol::NonTerminal(item.symbol))
+                            })
+                            .copied()
+                            .collect();
+                        for parent in parents {
+                            add(
+                                &mut chart,
+                                pos,
+                                Item {
+                                    dot: parent.dot + 1,
+                                    ..parent
+                                },
+                            );
+                        }
+                    }
+                    Some(Symbol::NonTerminal(next)) => {
+                        for alternative in 0..self.rules[*next].len() {
+                            add(
+                                &mut chart,
+                                pos,
+                                Item {
+                                    symbol: *next,
+                                    alternative,
+                                    dot: 0,
+                                    origin: pos,
+                                },
+                            );
+                        }
+                        // nullable nonterminals complete before their parent
+                        // is in the chart, so the parent is advanced here
+                        if self.nullable[*next] {
+                            add(&mut chart, pos, advanced);
+                        }
+                    }
+                    Some(Symbol::Terminal(t)) => {
+                        if input[pos..].starts_with(t) {
+                            add(&mut chart, pos + t.len(), advanced);
+                        }
+                    }
+                }
+            }
+        }
+        completed
+    }
+}
+
+impl Node {
+    pub fn symbol(&self) -> Option<usize> {
+        match self {
+            Node::Terminal(_) => None,
+            Node::NonTerminal { symbol, .. } => Some(*symbol),
+        }
+    }
+
+    pub fn unparse(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.unparse_into(&mut bytes);
+        bytes
+    }
+
+    fn unparse_into(&self, bytes: &mut Vec<u8>) {
+        match self {
+            Node::Terminal(t) => bytes.extend_from_slice(t),
+            Node::NonTerminal { children, .. } => {
+                for child in children {
+                    child.unparse_into(bytes);
+                }
+            }
+        }
+    }
+
+    pub fn depth(&self) -> usize {
+        match self {
+            Node::Terminal(_) => 0,
+            Node::NonTerminal { children, .. } => {
+                1 + children.iter().map(Node::depth).max().unwrap_or_default()
+            }
+        }
+    }
+
+    // the path of child indices to every nonterminal, in preorder
+    pub fn paths(&self) -> Vec<Vec<usize>> {
+        let mut paths = Vec::new();
+        self.collect_paths(&mut Vec::new(), &mut paths);
+        paths
+    }
+
+    fn collect_paths(&self, path: &mut Vec<usize>, paths: &mut Vec<Vec<usize>>) {
+        if let Node::NonTerminal { children, .. } = self {
+            paths.push(path.clone());
+            for (i, child) in children.iter().enumerate() {
+                path.push(i);
+                child.collect_paths(path, paths);
+                path.pop();
+            }
+        }
+    }
+
+    pub fn get(&self, path: &[usize]) -> Option<&Node> {
+        match (path.split_first(), self) {
+            (None, _) => Some(self),
+            (Some((i, rest)), Node::NonTerminal { children, .. }) => children.get(*i)?.get(rest),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, path: &[usize]) -> Option<&mut Node> {
+        match (path.split_first(), self) {
+            (None, node) => Some(node),
+            (Some((i, rest)), Node::NonTerminal { children, .. }) => {
+                children.get_mut(*i)?.get_mut(rest)
+            }
+            _ => None,
+        }
+    }
+
+    // where the node at `path` starts in the generated input
+    pub fn offset(&self, path: &[usize]) -> usize {
+        match (path.split_first(), self) {
+            (Some((i, rest)), Node::NonTerminal { children, .. }) => {
+                let before: usize = children
+                    .iter()
+                    .take(*i)
+                    .map(|child| child.unparse().len())
+                    .sum();
+                before + children.get(*i).map_or(0, |child| child.offset(rest))
+            }
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Item {
+    symbol: usize,
+    alternative: usize,
+    dot: usize,
+    origin: usize,
+}
+
+// Picks a derivation out of the recognizer's completed items. Derivations
+// that loop back on a nonterminal spanning the same bytes are cut off, one
+// without the loop exists whenever the input parses.
+struct TreeBuilder<'a> {
+    grammar: &'a Grammar,
+    input: &'a [u8],
+    completed: &'a HashMap<(usize, usize), Vec<(usize, usize)>>,
+    active: HashSet<(usize, usize, usize)>,
+    failed: HashSet<(usize, usize, usize)>,
+}
+
+impl TreeBuilder<'_> {
+    fn build(&mut self, symbol: usize, start: usize, end: usize, depth: usize) -> Option<Node> {
+        let key = (symbol, start, end);
+        if depth > MAX_DERIVATION_DEPTH || self.failed.contains(&key) || !self.active.insert(key) {
+            return None;
+        }
+        let alternatives: Vec<usize> = self
+            .completed
+            .get(&(symbol, start))
+            .into_iter()
+            .flatten()
+            .filter(|(_, e)| *e == end)
+            .map(|(alternative, _)| *alternative)
+            .collect();
+
+        let mut node = None;
+        for alternative in alternatives {
+            let symbols = &self.grammar.rules[symbol][alternative];
+            if let Some(children) = self.children(symbols, start, end, depth) {
+                node = Some(Node::NonTerminal { symbol, children });
+                break;
+            }
+        }
+        self.active.remove(&key);
+        if node.is_none() {
+            self.failed.insert(key);
+        }
+        node
+    }
+
+    // splits input[pos..end] between the symbols of an alternative
+    fn children(
+        &mut self,
+        symbols: &[Symbol],
+        pos: usize,
+        end: usize,
+        depth: usize,
+    ) -> Option<Vec<Node>> {
+        let Some((first, rest)) = symbols.split_first() else {
+            return (pos == end).then(Vec::new);
+        };
+        match first {
+            Symbol::Terminal(t) => {
+                if !self.input[pos..end].starts_with(t) {
+                    return None;
+                }
+                let mut children = self.children(rest, pos + t.len(), end, depth)?;
+                children.insert(0, Node::Terminal(t.clone()));
+                Some(children)
+            }
+            Symbol::NonTerminal(n) => {
+                let mut ends: Vec<usize> = self
+                    .completed
+                    .get(&(*n, pos))
+                    .into_iter()
+                    .flatten()
+                    .map(|(_, e)| *e)
+                    .filter(|e| *e <= end)
+                    .collect();
+                ends.sort_unstable();
+                ends.dedup();
+                for mid in ends {
+                    let Some(mut children) = self.children(rest, mid, end, depth) else {
+                        continue;
+                    };
+                    if let Some(child) = self.build(*n, pos, mid, depth + 1) {
+                        children.insert(0, child);
+                        return Some(children);
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+// the fewest levels of nonterminals each alternative needs before only
+// terminals are left, usize::MAX when it never gets there
+fn min_depths(rules: &[Vec<Vec<Symbol>>]) -> Vec<Vec<usize>> {
+    let mut symbol_depths = vec![usize::MAX; rules.len()];
+    let alternative_depth = |symbol_depths: &[usize], alternative: &[Symbol]| {
+        alternative
+            .iter()
+            .map(|s| match s {
+                Symbol::Terminal(_) => 0,
+                Symbol::NonTerminal(n) => symbol_depths[*n],
+            })
+            .max()
+            .unwrap_or_default()
+            .saturating_add(1)
+    };
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (symbol, alternatives) in rules.iter().enumerate() {
+            for alternative in alternatives {
+                let depth = alternative_depth(&symbol_depths, alternative);
+                if depth < symbol_depths[symbol] {
+                    symbol_depths[symbol] = depth;
+                    changed = true;
+                }
+            }
+        }
+    }
+    rules
+        .iter()
+        .map(|alternatives| {
+            alternatives
+                .iter()
+                .map(|alternative| alternative_depth(&symbol_depths, alternative))
+                .collect()
+        })
+        .collect()
+}
+
+fn parse_bnf(contents: &str) -> Result<Vec<RawRule>> {
+    let tokens = tokenize(contents)?;
+    let mut rules: Vec<RawRule> = Vec::new();
+    let mut i = 0;
+    while let Some((line, token)) = tokens.get(i) {
+        i += 1;
+        if let (Token::NonTerminal(name), Some((_, Token::Define))) = (token, tokens.get(i)) {
+            i += 1;
+            rules.push((name.clone(), vec![Vec::new()]));
+            continue;
+        }
+        let Some(alternative) = rules
+            .last_mut()
+            .and_then(|(_, alternatives)| alternatives.last_mut())
+        else {
+            return Err(anyhow!(
+                "line {line}: expected a rule like `<name> ::= ...`"
+            ));
+        };
+        match token {
+            Token::NonTerminal(name) => alternative.push(RawSymbol::NonTerminal(name.clone())),
+            Token::Terminal(t) => alternative.push(RawSymbol::Terminal(t.clone())),
+            Token::Alternative => {
+                if let Some((_, alternatives)) = rules.last_mut() {
+                    alternatives.push(Vec::new());
+                }
+            }
+            Token::Define => return Err(anyhow!("line {line}: `::=` has to follow a nonterminal")),
+        }
+    }
+    Ok(rules)
+}
+
+fn tokenize(contents: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut line = 1;
+    let mut chars = contents.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '<' => {
+                let mut name = String::from("<");
+                loop {
+                    match chars.next() {
+                        Some('>') => break,
+                        Some(c) if c != '\n' && c != '<' => name.push(c),
+                        _ => return Err(anyhow!("line {line}: unterminated nonterminal")),
+                    }
+                }
+                name.push('>');
+                tokens.push((line, Token::NonTerminal(name)));
+            }
+            '"' => {
+                let mut raw = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            raw.push('\\');
+                            raw.extend(chars.next());
+                        }
+                        Some(c) if c != '\n' => raw.push(c),
+                        _ => return Err(anyhow!("line {line}: unterminated terminal")),
+                    }
+                }
+                let terminal = unescape(&raw).map_err(|e| anyhow!("line {line}: {e}"))?;
+                tokens.push((line, Token::Terminal(terminal)));
+            }
+            ':' if chars.next_if_eq(&':').is_some() && chars.next_if_eq(&'=').is_some() => {
+                tokens.push((line, Token::Define));
+            }
+            '|' => tokens.push((line, Token::Alternative)),
+            c => return Err(anyhow!("line {line}: unexpected `{c}`")),
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_json(contents: &str) -> Result<Vec<RawRule>> {
+    let mut grammar: BTreeMap<String, Vec<String>> = serde_json::from_str(contents)?;
+    let start = grammar
+        .remove_entry("<start>")
+        .ok_or_else(|| anyhow!("<start> is never defined"))?;
+    Ok(std::iter::once(start)
+        .chain(grammar)
+        .map(|(name, expansions)| {
+            let alternatives = expansions.iter().map(|e| split_expansion(e)).collect();
+            (name, alternatives)
+        })
+        .collect())
+}
+
+// `<term> + <expr>` is the nonterminals and the text around them
+fn split_expansion(expansion: &str) -> Vec<RawSymbol> {
+    let mut symbols = Vec::new();
+    let mut last = 0;
+    for nonterminal in NONTERMINAL_REGEX.find_iter(expansion) {
+        symbols.push(RawSymbol::Terminal(
+            expansion.as_bytes()[last..nonterminal.start()].to_vec(),
+        ));
+        symbols.push(RawSymbol::NonTerminal(nonterminal.as_str().to_string()));
+        last = nonterminal.end();
+    }
+    symbols.push(RawSymbol::Terminal(expansion.as_bytes()[last..].to_vec()));
+    symbols
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn grammar(bnf: &str) -> Grammar {
+        parse_bnf(bnf).and_then(Grammar::new).unwrap()
+    }
+
+    fn assert_round_trips(grammar: &Grammar) {
+        let mut rng = SmallRng::seed_from_u64(0);
+        for _ in 0..100 {
+            let input = grammar
+                .generate(&mut rng, grammar.start, GENERATE_DEPTH)
+                .unparse();
+            let derivation = grammar
+                .parse(&input)
+                .unwrap_or_else(|| panic!("can't parse {:?}", String::from_utf8_lossy(&input)));
+            assert_eq!(derivation.unparse(), input);
+        }
+    }
+
+    #[test]
+    fn generated_inputs_parse_back() {
+        assert_round_trips(&grammar(
+            r#"<expr> ::= <term> "+" <expr> | <term>
+               <term> ::= "(" <expr> ")" | "1" | "\x00""#,
+        ));
+    }
+
+    #[test]
+    fn nullable_rules_round_trip() {
+        let grammar = grammar(
+            r#"<list> ::= <item> <list> | ""
+               <item> ::= <opt> "a" <opt> | <opt>
+               <opt> ::= "" | "b""#,
+        );
+        assert!(grammar.parse(b"").is_some());
+        assert_round_trips(&grammar);
+    }
+
+    #[test]
+    fn left_recursive_rules_round_trip() {
+        let grammar = grammar(
+            r#"<sum> ::= <sum> "+" <num> | <num>
+               <num> ::= <num> <digit> | <digit>
+               <digit> ::= "1" | "2""#,
+        );
+        assert!(grammar.parse(b"12+1+21").is_some());
+        assert!(grammar.parse(b"1++2").is_none());
+        assert_round_trips(&grammar);
+    }
+
+    #[test]
+    fn saved_json_grammar_keeps_its_indices() {
+        let dir = tempfile::tempdir().unwrap();
+        let json = dir.path().join("grammar.json");
+        fs::write(
+            &json,
+            r#"{"<start>": ["<expr>"],
+                "<expr>": ["<term> + <expr>", "<term>"],
+                "<term>": ["(<expr>)", "\"1\"", "\\", ""]}"#,
+        )
+        .unwrap();
+        let loaded = Grammar::load(json.to_str().unwrap()).unwrap();
+
+        let bnf = dir.path().join(GRAMMAR_FILE);
+        loaded.save(&bnf).unwrap();
+        let reloaded = Grammar::load(bnf.to_str().unwrap()).unwrap();
+
+        assert_eq!(reloaded.names, loaded.names);
+        assert_eq!(reloaded.rules, loaded.rules);
+        assert_eq!(reloaded.start, loaded.start);
+    }
+}
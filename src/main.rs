@@ -1,21 +1,37 @@
 use std::path::PathBuf;
 
 use analysis::CrashAnalyzer;
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 use clap::Parser;
+use config::Command;
 use engine::run_engine_for;
-use formats::{jpeg::Jpeg, png::Png, string::FuzzString, txt::Txt};
+use formats::{
+    external::External, grammar::GrammarInput, jpeg::Jpeg, png::Png, string::FuzzString, txt::Txt,
+};
 use log::{error, info};
 use types::{Config, FuzzType};
 
 mod analysis;
+mod autodict;
+mod cmplog;
 mod config;
+mod corpus;
+mod debugger;
+mod dictionary;
+mod diff;
 mod engine;
 mod errors;
+mod external;
 mod formats;
+mod grammar;
 mod mutate;
 mod mutations;
+mod oracle;
+mod regress;
+mod replay;
+mod reports;
 mod target;
+mod triage;
 mod types;
 mod utils;
 
@@ -29,12 +45,25 @@ fn main() {
 }
 
 fn run() -> Result<()> {
-    let mut config = config::RawConfig::parse().validate()?;
+    let cli = config::Cli::parse();
+    let raw_config = match cli.command {
+        Some(Command::Regress(args)) => return regress::run_regress(&args),
+        Some(Command::Diff(args)) => return diff::run_diff(&args),
+        Some(Command::Replay(args)) => return replay::run_replay(&args),
+        None => cli
+            .fuzz
+            .ok_or_else(|| anyhow!("missing fuzzing arguments"))?,
+    };
+
+    let mut config = raw_config.validate()?;
     utils::initialize(&mut config)?;
-    let mut analyzer = analysis::CrashAnalyzer::new(PathBuf::from(&config.report_path));
+    let mut analyzer =
+        analysis::CrashAnalyzer::new(PathBuf::from(&config.report_path), config.oracles.clone());
     info!("Parsed config and initialized analyzer successfully");
 
     run_engine(&mut analyzer, &mut config)?;
+    analyzer.finish();
+    analyzer.sort_by_severity();
     utils::create_run_json(&analyzer, &config)?;
     utils::print_report(&analyzer, &config)?;
 
@@ -44,6 +73,8 @@ fn run() -> Result<()> {
 /// if adding a new format type, extend this
 fn run_engine(analyzer: &mut CrashAnalyzer, config: &mut Config) -> Result<()> {
     match config.validated_fuzz_type {
+        FuzzType::External => run_engine_for::<External>(analyzer, config),
+        FuzzType::Grammar => run_engine_for::<GrammarInput>(analyzer, config),
         FuzzType::Jpeg => run_engine_for::<Jpeg>(analyzer, config),
         FuzzType::Png => run_engine_for::<Png>(analyzer, config),
         FuzzType::String => run_engine_for::<FuzzString>(analyzer, config),
@@ -1,35 +1,190 @@
+use std::{collections::BTreeMap, fmt, rc::Rc};
+
 use rand::{Rng, rngs::SmallRng};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+
+use crate::mutations::{
+    MutationContext, MutatorRegistry,
+    bytes::{BYTE_OPS, dict_insert, dict_overwrite, splice, splice_block},
+};
+
+// A single mutation applied to an input. `mutator` identifies the mutation
+// (eg. `png::change_depth`), `target` the field, chunk or segment it touched
+// and `offset` the byte it touched, when either makes sense. `params` holds
+// the values the mutator picked, and `rng_seed` seeds the rng the mutator was
+// given so the exact same mutation can be re-applied.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct MutationRecord {
+    pub mutator: String,
+    #[serde(default)]
+    pub target: Option<String>,
+    #[serde(default)]
+    pub offset: Option<usize>,
+    #[serde(default)]
+    pub params: BTreeMap<String, Value>,
+    #[serde(default)]
+    pub rng_seed: u64,
+}
+
+impl MutationRecord {
+    pub fn new(mutator: &str) -> Self {
+        Self {
+            mutator: mutator.to_string(),
+            target: None,
+            offset: None,
+            params: BTreeMap::new(),
+            rng_seed: 0,
+        }
+    }
+
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    pub fn param(mut self, name: &str, value: impl Into<Value>) -> Self {
+        self.params.insert(name.to_string(), value.into());
+        self
+    }
+}
 
-pub fn mutate_bytes(rng: &mut SmallRng, bytes: &mut [u8]) -> String {
-    let mut mutation_desc = String::new();
-    let index = rng.random_range(0..bytes.len());
-    match rng.random_range(0..4) {
-        0 => {
-            // bitmask mutation
-            let mask: u8 = rng.random();
-            bytes[index] ^= mask;
-            mutation_desc = format!("applying a bitmask at index {index}");
+impl fmt::Display for MutationRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.mutator)?;
+        if let Some(target) = &self.target {
+            write!(f, " on {target}")?;
         }
-        1 => {
-            // bit flip
-            let bit_index = rng.random_range(0..8);
-            let mutated_byte = bytes[index] ^ (1 << bit_index);
-            bytes[index] = mutated_byte;
-            mutation_desc = format!("applying a bitflip at index {index}");
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {offset}")?;
         }
-        2 => {
-            // byte insertion
-            let new_byte: u8 = rng.random();
-            bytes[index..].rotate_right(1);
-            bytes[index] = new_byte;
-            mutation_desc = format!("inserting the byte <{new_byte}> at index {index}");
+        for (i, (name, value)) in self.params.iter().enumerate() {
+            let sep = if i == 0 { " with " } else { ", " };
+            write!(f, "{sep}{name}={value}")?;
         }
-        3 => {
-            // byte shift
-            bytes.rotate_left(1);
-            mutation_desc = "shifting everything left by 1".to_string();
+        Ok(())
+    }
+}
+
+// reports written before mutations were structured store a plain description
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredMutation {
+    Record(MutationRecord),
+    Description(String),
+}
+
+pub fn deserialize_records<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<MutationRecord>, D::Error> {
+    let stored = Vec::<StoredMutation>::deserialize(deserializer)?;
+    Ok(stored
+        .into_iter()
+        .map(|mutation| match mutation {
+            StoredMutation::Record(record) => record,
+            StoredMutation::Description(desc) => {
+                MutationRecord::new("legacy").param("description", desc)
+            }
+        })
+        .collect())
+}
+
+// most havoc rounds stack a handful of ops, a few stack a lot more
+const HAVOC_STACK_POW_MAX: u32 = 5;
+// havoc is picked this much more often than any single byte op
+const HAVOC_WEIGHT: u32 = 8;
+
+// dictionary tokens are picked this much more often than any single byte op
+const DICT_WEIGHT: u32 = 2;
+
+// splicing is picked this much more often than any single byte op
+const SPLICE_WEIGHT: u32 = 2;
+
+// registers the generic byte level mutators for a model, `bytes` returns the
+// buffer they work on. the token and splice mutators are only registered when
+// there are tokens, or other corpus entries, to use
+pub fn byte_mutators<M: 'static>(
+    context: &Rc<MutationContext>,
+    bytes: fn(&mut M) -> &mut Vec<u8>,
+) -> MutatorRegistry<M> {
+    let mut registry = MutatorRegistry::new();
+    for (name, op) in BYTE_OPS {
+        registry = registry.register(name, 1, move |rng: &mut SmallRng, model: &mut M| {
+            Ok(op(rng, bytes(model)))
+        });
+    }
+    registry = registry.register(
+        "bytes::havoc",
+        HAVOC_WEIGHT,
+        move |rng: &mut SmallRng, model: &mut M| Ok(Some(mutate_bytes(rng, bytes(model)))),
+    );
+
+    if !context.dictionary.is_empty() {
+        let insert_context = Rc::clone(context);
+        let overwrite_context = Rc::clone(context);
+        registry = registry
+            .register(
+                "bytes::dict_insert",
+                DICT_WEIGHT,
+                move |rng: &mut SmallRng, model: &mut M| {
+                    Ok(dict_insert(rng, bytes(model), &insert_context.dictionary))
+                },
+            )
+            .register(
+                "bytes::dict_overwrite",
+                DICT_WEIGHT,
+                move |rng: &mut SmallRng, model: &mut M| {
+                    Ok(dict_overwrite(
+                        rng,
+                        bytes(model),
+                        &overwrite_context.dictionary,
+                    ))
+                },
+            );
+    }
+
+    if context.corpus.len() > 1 {
+        let splice_context = Rc::clone(context);
+        let block_context = Rc::clone(context);
+        registry = registry
+            .register(
+                "bytes::splice",
+                SPLICE_WEIGHT,
+                move |rng: &mut SmallRng, model: &mut M| {
+                    Ok(splice(rng, bytes(model), &splice_context.corpus))
+                },
+            )
+            .register(
+                "bytes::splice_block",
+                SPLICE_WEIGHT,
+                move |rng: &mut SmallRng, model: &mut M| {
+                    Ok(splice_block(rng, bytes(model), &block_context.corpus))
+                },
+            );
+    }
+    registry
+}
+
+// AFL style havoc, applies a random stack of byte ops. the ops that couldn't
+// be applied (eg. deleting from an empty input) are left out of the record
+pub fn mutate_bytes(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> MutationRecord {
+    // the lower of two powers, so 2 ops are picked 9 times as often as 32
+    let pow = rng
+        .random_range(1..=HAVOC_STACK_POW_MAX)
+        .min(rng.random_range(1..=HAVOC_STACK_POW_MAX));
+    let stack = 1 << pow;
+    let mut ops = Vec::new();
+    for _ in 0..stack {
+        let (_, op) = BYTE_OPS[rng.random_range(0..BYTE_OPS.len())];
+        if let Some(record) = op(rng, bytes) {
+            ops.push(record);
         }
-        _ => {}
     }
-    mutation_desc
+
+    MutationRecord::new("bytes::havoc").param("ops", serde_json::to_value(ops).unwrap_or_default())
 }
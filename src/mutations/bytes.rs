@@ -0,0 +1,447 @@
+// every op shares the ByteOp signature, even the ones that never resize
+#![allow(clippy::ptr_arg)]
+
+use rand::{Rng, rngs::SmallRng, seq::IndexedRandom};
+
+use crate::{
+    corpus::Corpus,
+    dictionary::{Dictionary, escape},
+    mutate::MutationRecord,
+};
+
+// inputs are never grown past this
+pub const MAX_INPUT_LEN: usize = 1024 * 1024;
+// largest delta added or subtracted by the arithmetic mutations
+pub const ARITH_MAX: u64 = 35;
+// largest block moved around by the block mutations
+const BLOCK_MAX: usize = 1024;
+
+// values that tend to sit on boundaries, as signed integers of each width
+const INTERESTING_8: [i64; 9] = [-128, -1, 0, 1, 16, 32, 64, 100, 127];
+const INTERESTING_16: [i64; 10] = [-32768, -129, 128, 255, 256, 512, 1000, 1024, 4096, 32767];
+const INTERESTING_32: [i64; 8] = [
+    -2_147_483_648,
+    -100_663_046,
+    -32769,
+    32768,
+    65535,
+    65536,
+    100_663_045,
+    2_147_483_647,
+];
+const INTERESTING_64: [i64; 4] = [i64::MIN, -4_294_967_296, 4_294_967_296, i64::MAX];
+
+// the byte ops, all of them can be stacked by havoc
+pub type ByteOp = fn(&mut SmallRng, &mut Vec<u8>) -> Option<MutationRecord>;
+pub const BYTE_OPS: [(&str, ByteOp); 20] = [
+    ("bytes::bitflip", bitflip),
+    ("bytes::bitmask", bitmask),
+    ("bytes::arith8", |rng, bytes| arith(rng, bytes, 1)),
+    ("bytes::arith16", |rng, bytes| arith(rng, bytes, 2)),
+    ("bytes::arith32", |rng, bytes| arith(rng, bytes, 4)),
+    ("bytes::arith64", |rng, bytes| arith(rng, bytes, 8)),
+    ("bytes::interesting8", |rng, bytes| {
+        interesting(rng, bytes, 1)
+    }),
+    ("bytes::interesting16", |rng, bytes| {
+        interesting(rng, bytes, 2)
+    }),
+    ("bytes::interesting32", |rng, bytes| {
+        interesting(rng, bytes, 4)
+    }),
+    ("bytes::interesting64", |rng, bytes| {
+        interesting(rng, bytes, 8)
+    }),
+    ("bytes::insert", insert),
+    ("bytes::rotate_left", rotate_left),
+    ("bytes::delete_block", delete_block),
+    ("bytes::duplicate_block", duplicate_block),
+    ("bytes::copy_block", copy_block),
+    ("bytes::insert_block", insert_block),
+    ("bytes::overwrite_block", overwrite_block),
+    ("bytes::grow", grow),
+    ("bytes::shrink", shrink),
+    ("bytes::swap_bytes", swap_bytes),
+];
+
+pub fn bitflip(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    let index = rng.random_range(0..bytes.len());
+    let bit_index = rng.random_range(0..8);
+    bytes[index] ^= 1 << bit_index;
+    Some(
+        MutationRecord::new("bytes::bitflip")
+            .offset(index)
+            .param("bit", bit_index),
+    )
+}
+
+pub fn bitmask(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    let index = rng.random_range(0..bytes.len());
+    let mask: u8 = rng.random_range(1..=u8::MAX);
+    bytes[index] ^= mask;
+    Some(
+        MutationRecord::new("bytes::bitmask")
+            .offset(index)
+            .param("mask", mask),
+    )
+}
+
+// adds or subtracts a small value to an integer of `width` bytes
+pub fn arith(rng: &mut SmallRng, bytes: &mut Vec<u8>, width: usize) -> Option<MutationRecord> {
+    if bytes.len() < width {
+        return None;
+    }
+
+    let index = rng.random_range(0..=bytes.len() - width);
+    let big_endian = width > 1 && rng.random_bool(0.5);
+    let delta = rng.random_range(1..=ARITH_MAX);
+    let subtract = rng.random_bool(0.5);
+
+    let value = read_int(&bytes[index..index + width], big_endian);
+    let value = if subtract {
+        value.wrapping_sub(delta)
+    } else {
+        value.wrapping_add(delta)
+    };
+    write_int(&mut bytes[index..index + width], value, big_endian);
+
+    let delta = i64::try_from(delta).unwrap_or_default();
+    Some(
+        MutationRecord::new(&format!("bytes::arith{}", width * 8))
+            .offset(index)
+            .param("delta", if subtract { -delta } else { delta })
+            .param("endian", endian(big_endian)),
+    )
+}
+
+// replaces an integer of `width` bytes with one of the interesting values
+pub fn interesting(
+    rng: &mut SmallRng,
+    bytes: &mut Vec<u8>,
+    width: usize,
+) -> Option<MutationRecord> {
+    if bytes.len() < width {
+        return None;
+    }
+
+    let values = interesting_values(width);
+    let index = rng.random_range(0..=bytes.len() - width);
+    let big_endian = width > 1 && rng.random_bool(0.5);
+    let value = *values.choose(rng)?;
+    write_int(
+        &mut bytes[index..index + width],
+        value.cast_unsigned(),
+        big_endian,
+    );
+
+    Some(
+        MutationRecord::new(&format!("bytes::interesting{}", width * 8))
+            .offset(index)
+            .param("value", value)
+            .param("endian", endian(big_endian)),
+    )
+}
+
+pub fn insert(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    if bytes.len() >= MAX_INPUT_LEN {
+        return None;
+    }
+
+    let index = rng.random_range(0..=bytes.len());
+    let new_byte: u8 = rng.random();
+    bytes.insert(index, new_byte);
+    Some(
+        MutationRecord::new("bytes::insert")
+            .offset(index)
+            .param("byte", new_byte),
+    )
+}
+
+pub fn rotate_left(_rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    if bytes.len() < 2 {
+        return None;
+    }
+
+    bytes.rotate_left(1);
+    Some(MutationRecord::new("bytes::rotate_left").param("count", 1))
+}
+
+pub fn delete_block(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    // leave at least a byte behind, shrink covers emptying the input
+    if bytes.len() < 2 {
+        return None;
+    }
+
+    let len = block_len(rng, bytes.len() - 1);
+    let index = rng.random_range(0..=bytes.len() - len);
+    bytes.drain(index..index + len);
+    Some(
+        MutationRecord::new("bytes::delete_block")
+            .offset(index)
+            .param("len", len),
+    )
+}
+
+// inserts a copy of a block somewhere else in the input
+pub fn duplicate_block(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    if bytes.is_empty() || bytes.len() >= MAX_INPUT_LEN {
+        return None;
+    }
+
+    let len = block_len(rng, bytes.len().min(MAX_INPUT_LEN - bytes.len()));
+    let from = rng.random_range(0..=bytes.len() - len);
+    let index = rng.random_range(0..=bytes.len());
+    let block = bytes[from..from + len].to_vec();
+    bytes.splice(index..index, block);
+    Some(
+        MutationRecord::new("bytes::duplicate_block")
+            .offset(index)
+            .param("from", from)
+            .param("len", len),
+    )
+}
+
+// overwrites a block with another block of the input
+pub fn copy_block(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    if bytes.len() < 2 {
+        return None;
+    }
+
+    let len = block_len(rng, bytes.len() - 1);
+    let from = rng.random_range(0..=bytes.len() - len);
+    let index = rng.random_range(0..=bytes.len() - len);
+    bytes.copy_within(from..from + len, index);
+    Some(
+        MutationRecord::new("bytes::copy_block")
+            .offset(index)
+            .param("from", from)
+            .param("len", len),
+    )
+}
+
+// inserts a block of one repeated byte
+pub fn insert_block(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    if bytes.len() >= MAX_INPUT_LEN {
+        return None;
+    }
+
+    let len = block_len(rng, MAX_INPUT_LEN - bytes.len());
+    let index = rng.random_range(0..=bytes.len());
+    let byte = random_fill_byte(rng, bytes);
+    bytes.splice(index..index, std::iter::repeat_n(byte, len));
+    Some(
+        MutationRecord::new("bytes::insert_block")
+            .offset(index)
+            .param("len", len)
+            .param("byte", byte),
+    )
+}
+
+// overwrites a block with one repeated byte
+pub fn overwrite_block(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    let len = block_len(rng, bytes.len());
+    let index = rng.random_range(0..=bytes.len() - len);
+    let byte = random_fill_byte(rng, bytes);
+    bytes[index..index + len].fill(byte);
+    Some(
+        MutationRecord::new("bytes::overwrite_block")
+            .offset(index)
+            .param("len", len)
+            .param("byte", byte),
+    )
+}
+
+// appends random bytes
+pub fn grow(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    if bytes.len() >= MAX_INPUT_LEN {
+        return None;
+    }
+
+    let len = block_len(rng, MAX_INPUT_LEN - bytes.len());
+    let offset = bytes.len();
+    bytes.extend((0..len).map(|_| rng.random::<u8>()));
+    Some(
+        MutationRecord::new("bytes::grow")
+            .offset(offset)
+            .param("len", len),
+    )
+}
+
+// truncates the input to a random length
+pub fn shrink(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    if bytes.is_empty() {
+        return None;
+    }
+
+    let len = rng.random_range(0..bytes.len());
+    bytes.truncate(len);
+    Some(MutationRecord::new("bytes::shrink").param("len", len))
+}
+
+pub fn swap_bytes(rng: &mut SmallRng, bytes: &mut Vec<u8>) -> Option<MutationRecord> {
+    if bytes.len() < 2 {
+        return None;
+    }
+
+    let first = rng.random_range(0..bytes.len());
+    let second = rng.random_range(0..bytes.len());
+    bytes.swap(first, second);
+    Some(
+        MutationRecord::new("bytes::swap_bytes")
+            .offset(first)
+            .param("with", second),
+    )
+}
+
+// wider integers can hold every narrower interesting value too
+pub fn interesting_values(width: usize) -> Vec<i64> {
+    let mut values = INTERESTING_8.to_vec();
+    if width >= 2 {
+        values.extend(INTERESTING_16);
+    }
+    if width >= 4 {
+        values.extend(INTERESTING_32);
+    }
+    if width >= 8 {
+        values.extend(INTERESTING_64);
+    }
+    values
+}
+
+// inserts a dictionary token at a random offset
+pub fn dict_insert(
+    rng: &mut SmallRng,
+    bytes: &mut Vec<u8>,
+    dictionary: &Dictionary,
+) -> Option<MutationRecord> {
+    let token = dictionary.choose(rng)?;
+    if bytes.len() + token.len() > MAX_INPUT_LEN {
+        return None;
+    }
+
+    let index = rng.random_range(0..=bytes.len());
+    bytes.splice(index..index, token.iter().copied());
+    Some(
+        MutationRecord::new("bytes::dict_insert")
+            .offset(index)
+            .param("token", escape(token)),
+    )
+}
+
+// overwrites the input with a dictionary token at a random offset
+pub fn dict_overwrite(
+    rng: &mut SmallRng,
+    bytes: &mut Vec<u8>,
+    dictionary: &Dictionary,
+) -> Option<MutationRecord> {
+    let token = dictionary.choose(rng)?;
+    if bytes.len() < token.len() {
+        return None;
+    }
+
+    let index = rng.random_range(0..=bytes.len() - token.len());
+    bytes[index..index + token.len()].copy_from_slice(token);
+    Some(
+        MutationRecord::new("bytes::dict_overwrite")
+            .offset(index)
+            .param("token", escape(token)),
+    )
+}
+
+// keeps the head of the input and appends the tail of another corpus entry
+pub fn splice(rng: &mut SmallRng, bytes: &mut Vec<u8>, corpus: &Corpus) -> Option<MutationRecord> {
+    let (index, partner) = corpus.choose_partner(rng)?;
+    let partner = &partner.content;
+
+    let head = rng.random_range(0..=bytes.len().min(MAX_INPUT_LEN));
+    let from = rng.random_range(0..=partner.len());
+    let to = partner.len().min(from + MAX_INPUT_LEN - head);
+    bytes.truncate(head);
+    bytes.extend_from_slice(&partner[from..to]);
+    Some(
+        corpus.record_partner(
+            MutationRecord::new("bytes::splice")
+                .offset(head)
+                .param("from", from),
+            index,
+        ),
+    )
+}
+
+// inserts a block of another corpus entry
+pub fn splice_block(
+    rng: &mut SmallRng,
+    bytes: &mut Vec<u8>,
+    corpus: &Corpus,
+) -> Option<MutationRecord> {
+    let (index, partner) = corpus.choose_partner(rng)?;
+    let partner = &partner.content;
+    if partner.is_empty() || bytes.len() >= MAX_INPUT_LEN {
+        return None;
+    }
+
+    let len = block_len(rng, partner.len().min(MAX_INPUT_LEN - bytes.len()));
+    let from = rng.random_range(0..=partner.len() - len);
+    let offset = rng.random_range(0..=bytes.len());
+    bytes.splice(offset..offset, partner[from..from + len].iter().copied());
+    Some(
+        corpus.record_partner(
+            MutationRecord::new("bytes::splice_block")
+                .offset(offset)
+                .param("from", from)
+                .param("len", len),
+            index,
+        ),
+    )
+}
+
+// block lengths are biased towards small blocks, like afl's choose_block_len
+fn block_len(rng: &mut SmallRng, limit: usize) -> usize {
+    let max = match rng.random_range(0..3) {
+        0 => 32,
+        1 => 128,
+        _ => BLOCK_MAX,
+    };
+    rng.random_range(1..=max.min(limit).max(1))
+}
+
+// either a random byte or one already in the input
+fn random_fill_byte(rng: &mut SmallRng, bytes: &[u8]) -> u8 {
+    match bytes.choose(rng) {
+        Some(byte) if rng.random_bool(0.5) => *byte,
+        _ => rng.random(),
+    }
+}
+
+pub fn read_int(field: &[u8], big_endian: bool) -> u64 {
+    let mut buf = [0u8; 8];
+    buf[..field.len()].copy_from_slice(field);
+    if big_endian {
+        buf[..field.len()].reverse();
+    }
+    u64::from_le_bytes(buf)
+}
+
+pub fn write_int(field: &mut [u8], value: u64, big_endian: bool) {
+    let width = field.len();
+    field.copy_from_slice(&value.to_le_bytes()[..width]);
+    if big_endian {
+        field.reverse();
+    }
+}
+
+pub fn endian(big_endian: bool) -> &'static str {
+    if big_endian { "be" } else { "le" }
+}
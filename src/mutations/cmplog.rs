@@ -0,0 +1,185 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::{
+    cmplog::Comparison,
+    dictionary::{escape, unescape},
+    mutate::MutationRecord,
+    mutations::bytes::write_int,
+};
+
+// an operand that shows up all over the input is probably not what was compared
+const MAX_MATCHES: usize = 16;
+// caps the substitutions made on one input, for inputs the target compares a lot
+const MAX_CANDIDATES: usize = 4096;
+// substitutions stacked on a seed at most, an input with this many isn't
+// traced again
+pub const MAX_DEPTH: usize = 8;
+
+// One substitution: the bytes at `offset` matched an operand of a failed
+// comparison and are replaced by the other operand
+struct Candidate {
+    offset: usize,
+    original: Vec<u8>,
+    replacement: Vec<u8>,
+}
+
+// An input along with the substitutions that made it from the seed and the
+// ones left to try on it
+struct Solving {
+    input: Vec<u8>,
+    mutations: Vec<MutationRecord>,
+    candidates: std::vec::IntoIter<Candidate>,
+}
+
+// Input-to-state substitution, like redqueen. The operands the target
+// compared are searched for in the input and every match is replaced by the
+// value it was compared against. Inputs that make the target compare
+// something new are added back, so checks that guard each other (eg. a magic
+// value, then a version) get solved one after the other.
+#[derive(Default)]
+pub struct CmpLogStage {
+    queue: VecDeque<Solving>,
+    seen: HashSet<Comparison>, // comparisons already substituted
+}
+
+impl CmpLogStage {
+    // queues the substitutions for the comparisons the target made on an
+    // input, comparisons already substituted on an earlier input are skipped
+    pub fn add(
+        &mut self,
+        input: Vec<u8>,
+        mutations: Vec<MutationRecord>,
+        comparisons: &[Comparison],
+    ) {
+        let comparisons: Vec<Comparison> = comparisons
+            .iter()
+            .filter(|comparison| self.seen.insert((*comparison).clone()))
+            .cloned()
+            .collect();
+        let candidates = candidates(&input, &comparisons);
+        if !candidates.is_empty() {
+            self.queue.push_back(Solving {
+                input,
+                mutations,
+                candidates: candidates.into_iter(),
+            });
+        }
+    }
+}
+
+impl Iterator for CmpLogStage {
+    type Item = (Vec<u8>, Vec<MutationRecord>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let solving = self.queue.front_mut()?;
+            let Some(candidate) = solving.candidates.next() else {
+                self.queue.pop_front();
+                continue;
+            };
+
+            let mut bytes = solving.input.clone();
+            bytes[candidate.offset..candidate.offset + candidate.replacement.len()]
+                .copy_from_slice(&candidate.replacement);
+            let mut mutations = solving.mutations.clone();
+            mutations.push(
+                MutationRecord::new("cmplog::replace")
+                    .offset(candidate.offset)
+                    .param("original", escape(&candidate.original))
+                    .param("replacement", escape(&candidate.replacement)),
+            );
+            return Some((bytes, mutations));
+        }
+    }
+}
+
+fn candidates(input: &[u8], comparisons: &[Comparison]) -> Vec<Candidate> {
+    let mut candidates = Vec::new();
+    let mut seen = HashSet::new();
+    for comparison in comparisons {
+        for (pattern, replacement) in substitutions(comparison) {
+            if pattern.is_empty() || pattern.len() > input.len() {
+                continue;
+            }
+            let matches = input
+                .windows(pattern.len())
+                .enumerate()
+                .filter(|(_, window)| *window == pattern.as_slice())
+                .map(|(offset, _)| offset)
+                .take(MAX_MATCHES);
+            for offset in matches {
+                // replacements longer than the pattern are cut at the end of
+                // the input, the stage never resizes it
+                let end = (offset + replacement.len()).min(input.len());
+                let replacement = &replacement[..end - offset];
+                if input[offset..end] == *replacement
+                    || !seen.insert((offset, replacement.to_vec()))
+                {
+                    continue;
+                }
+                candidates.push(Candidate {
+                    offset,
+                    original: input[offset..end].to_vec(),
+                    replacement: replacement.to_vec(),
+                });
+                if candidates.len() >= MAX_CANDIDATES {
+                    return candidates;
+                }
+            }
+        }
+    }
+    candidates
+}
+
+// (pattern, replacement) pairs for both operands of a comparison. integers
+// are tried in both byte orders and at every narrower width both operands
+// fit in, since the compared value may have been widened after being read
+fn substitutions(comparison: &Comparison) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut substitutions = Vec::new();
+    match comparison {
+        Comparison::Int { width, a, b } => {
+            for size in [1, 2, 4, 8] {
+                let fits = size == 8 || (*a >> (size * 8) == 0 && *b >> (size * 8) == 0);
+                if size > *width || !fits {
+                    continue;
+                }
+                for big_endian in [false, true] {
+                    if size == 1 && big_endian {
+                        continue;
+                    }
+                    let encode = |value: u64| {
+                        let mut bytes = vec![0; size];
+                        write_int(&mut bytes, value, big_endian);
+                        bytes
+                    };
+                    substitutions.push((encode(*a), encode(*b)));
+                    substitutions.push((encode(*b), encode(*a)));
+                }
+            }
+        }
+        Comparison::Bytes { a, b } => {
+            substitutions.push((a.clone(), b.clone()));
+            substitutions.push((b.clone(), a.clone()));
+        }
+    }
+    substitutions
+}
+
+// re-applies a cmplog record, None if the record doesn't come from the stage
+// or its original bytes aren't in the input
+pub fn replay(input: &[u8], record: &MutationRecord) -> Option<Vec<u8>> {
+    if record.mutator != "cmplog::replace" {
+        return None;
+    }
+    let param = |name: &str| unescape(record.params.get(name)?.as_str()?).ok();
+    let (original, replacement) = (param("original")?, param("replacement")?);
+    let offset = record.offset?;
+
+    let mut bytes = input.to_vec();
+    let field = bytes.get_mut(offset..offset.checked_add(original.len())?)?;
+    if *field != original || original.len() != replacement.len() {
+        return None;
+    }
+    field.copy_from_slice(&replacement);
+    Some(bytes)
+}
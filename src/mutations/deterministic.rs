@@ -0,0 +1,188 @@
+use crate::{
+    mutate::MutationRecord,
+    mutations::bytes::{ARITH_MAX, endian, interesting_values, read_int, write_int},
+};
+
+// The deterministic stage every seed goes through before random mutation,
+// like afl's. Each pass walks every offset of the seed and each step is a
+// single mutation of the unmodified seed. Records keep the step number so
+// the input can be regenerated from the seed alone.
+#[derive(Clone, Copy)]
+enum Pass {
+    BitFlip,
+    ByteFlip,
+    Arith(usize),
+    Interesting(usize),
+}
+
+const PASSES: [Pass; 8] = [
+    Pass::BitFlip,
+    Pass::ByteFlip,
+    Pass::Arith(1),
+    Pass::Arith(2),
+    Pass::Arith(4),
+    Pass::Interesting(1),
+    Pass::Interesting(2),
+    Pass::Interesting(4),
+];
+
+// deltas are tried both added and subtracted
+#[allow(clippy::cast_possible_truncation)]
+const ARITH_VARIANTS: usize = 2 * ARITH_MAX as usize;
+
+impl Pass {
+    fn name(self) -> String {
+        match self {
+            Pass::BitFlip => "deterministic::bitflip".into(),
+            Pass::ByteFlip => "deterministic::byteflip".into(),
+            Pass::Arith(width) => format!("deterministic::arith{}", width * 8),
+            Pass::Interesting(width) => format!("deterministic::interesting{}", width * 8),
+        }
+    }
+
+    // number of steps the pass takes over an input of `len` bytes
+    fn steps(self, len: usize) -> usize {
+        match self {
+            Pass::BitFlip => len * 8,
+            Pass::ByteFlip => len,
+            Pass::Arith(width) => positions(len, width) * endians(width) * ARITH_VARIANTS,
+            Pass::Interesting(width) => {
+                positions(len, width) * endians(width) * interesting_values(width).len()
+            }
+        }
+    }
+
+    // the input after the given step, None when the step leaves it unchanged
+    fn apply(self, input: &[u8], step: usize) -> Option<(Vec<u8>, MutationRecord)> {
+        let mut bytes = input.to_vec();
+        let record = MutationRecord::new(&self.name());
+        let record = match self {
+            Pass::BitFlip => {
+                let (index, bit) = (step / 8, step % 8);
+                bytes[index] ^= 0x80 >> bit;
+                record.offset(index).param("bit", bit)
+            }
+            Pass::ByteFlip => {
+                bytes[step] ^= 0xFF;
+                record.offset(step)
+            }
+            Pass::Arith(width) => {
+                let per_position = endians(width) * ARITH_VARIANTS;
+                let index = step / per_position;
+                let big_endian = step % per_position >= ARITH_VARIANTS;
+                let variant = step % ARITH_VARIANTS;
+                let delta = (variant / 2 + 1) as u64;
+                let subtract = variant % 2 == 1;
+
+                let field = &mut bytes[index..index + width];
+                let value = read_int(field, big_endian);
+                let value = if subtract {
+                    value.wrapping_sub(delta)
+                } else {
+                    value.wrapping_add(delta)
+                };
+                write_int(field, value, big_endian);
+                if could_be_bitflip(&input[index..index + width], field) {
+                    return None;
+                }
+
+                let delta = i64::try_from(delta).unwrap_or_default();
+                record
+                    .offset(index)
+                    .param("delta", if subtract { -delta } else { delta })
+                    .param("endian", endian(big_endian))
+            }
+            Pass::Interesting(width) => {
+                let values = interesting_values(width);
+                let per_position = endians(width) * values.len();
+                let index = step / per_position;
+                let big_endian = step % per_position >= values.len();
+                let value = values[step % values.len()];
+
+                let field = &mut bytes[index..index + width];
+                write_int(field, value.cast_unsigned(), big_endian);
+                if could_be_bitflip(&input[index..index + width], field) {
+                    return None;
+                }
+                record
+                    .offset(index)
+                    .param("value", value)
+                    .param("endian", endian(big_endian))
+            }
+        };
+
+        (bytes != input).then(|| (bytes, record.param("step", step)))
+    }
+}
+
+// true when the bitflip or byteflip pass already made this change. like afl,
+// the arith and interesting passes skip these steps
+fn could_be_bitflip(old: &[u8], new: &[u8]) -> bool {
+    let diff = read_int(old, false) ^ read_int(new, false);
+    if diff == 0 {
+        return true;
+    }
+    let shift = diff.trailing_zeros();
+    let diff = diff >> shift;
+    diff == 1 || (diff == 0xFF && shift.is_multiple_of(8))
+}
+
+// offsets an integer of `width` bytes fits at
+fn positions(len: usize, width: usize) -> usize {
+    (len + 1).saturating_sub(width)
+}
+
+// single bytes have no byte order to try
+fn endians(width: usize) -> usize {
+    if width > 1 { 2 } else { 1 }
+}
+
+// Iterates over every input of the deterministic stage for a seed. Steps that
+// wouldn't change the seed or repeat an earlier pass are skipped.
+pub struct DeterministicStage {
+    input: Vec<u8>,
+    pass: usize,
+    step: usize,
+}
+
+impl DeterministicStage {
+    pub fn new(input: Vec<u8>) -> Self {
+        Self {
+            input,
+            pass: 0,
+            step: 0,
+        }
+    }
+}
+
+impl Iterator for DeterministicStage {
+    type Item = (Vec<u8>, MutationRecord);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(pass) = PASSES.get(self.pass) {
+            if self.step >= pass.steps(self.input.len()) {
+                self.pass += 1;
+                self.step = 0;
+                continue;
+            }
+
+            let step = self.step;
+            self.step += 1;
+            if let Some(mutated) = pass.apply(&self.input, step) {
+                return Some(mutated);
+            }
+        }
+        None
+    }
+}
+
+// regenerates the input of a deterministic record from its seed, None if the
+// record doesn't come from the deterministic stage
+pub fn replay(input: &[u8], record: &MutationRecord) -> Option<Vec<u8>> {
+    let pass = PASSES.iter().find(|pass| pass.name() == record.mutator)?;
+    let step = usize::try_from(record.params.get("step")?.as_u64()?).ok()?;
+    if step >= pass.steps(input.len()) {
+        return None;
+    }
+    pass.apply(input, step).map(|(bytes, _)| bytes)
+}
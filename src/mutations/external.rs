@@ -0,0 +1,52 @@
+use std::rc::Rc;
+
+use log::{debug, warn};
+use rand::{Rng, rngs::SmallRng};
+
+use crate::{
+    formats::template::FileFormat,
+    mutate::MutationRecord,
+    mutations::{MutationContext, MutatorRegistry, bytes::MAX_INPUT_LEN},
+};
+
+// external mutators are picked as often as havoc
+const EXTERNAL_WEIGHT: u32 = 8;
+
+// The format's mutators along with the external ones. External mutators work
+// on the generated bytes, their output is parsed back into a model and
+// mutations whose output doesn't parse or that fail count as not applied.
+pub fn mutators<F: FileFormat>(context: &Rc<MutationContext>) -> MutatorRegistry<F::Model> {
+    let mut registry = F::mutators(context);
+    for external in &context.external {
+        let external = Rc::clone(external);
+        registry = registry.register(
+            external.name.clone(),
+            EXTERNAL_WEIGHT,
+            move |rng: &mut SmallRng, model: &mut F::Model| {
+                let input = F::generate(model.clone())?;
+                // a failing mutator only fails its own mutation
+                let (mutated, description) =
+                    match external.mutate(&input, rng.random(), MAX_INPUT_LEN) {
+                        Ok(Some(mutated)) => mutated,
+                        Ok(None) => return Ok(None),
+                        Err(e) => {
+                            warn!("{e}");
+                            return Ok(None);
+                        }
+                    };
+
+                match F::parse(&mutated) {
+                    Ok(parsed) => *model = parsed,
+                    Err(e) => {
+                        debug!("Output of {} doesn't parse: {e}", external.name);
+                        return Ok(None);
+                    }
+                }
+                Ok(Some(
+                    MutationRecord::new(&external.name).param("description", description),
+                ))
+            },
+        );
+    }
+    registry
+}
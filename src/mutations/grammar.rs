@@ -0,0 +1,170 @@
+use std::collections::HashSet;
+
+use rand::{Rng, rngs::SmallRng, seq::IndexedRandom};
+
+use crate::{
+    formats::grammar::GrammarModel,
+    grammar::{GENERATE_DEPTH, Grammar, MAX_DERIVATION_DEPTH, MAX_DERIVATION_LEN, Node},
+    mutate::MutationRecord,
+    mutations::MutationContext,
+};
+
+// times the recursive part of a derivation is repeated at most
+const MAX_RECURSION_REPEATS: usize = 4;
+
+// regenerates a random nonterminal of the derivation
+pub fn replace_subtree(
+    rng: &mut SmallRng,
+    model: &mut GrammarModel,
+    grammar: &Grammar,
+) -> Option<MutationRecord> {
+    let mut derivation = derivation(model, grammar)?;
+    let paths = derivation.paths();
+    let node = rng.random_range(0..paths.len().max(1));
+    let path = paths.get(node)?;
+    let target = derivation.get_mut(path)?;
+    let symbol = target.symbol()?;
+    *target = grammar.generate(rng, symbol, GENERATE_DEPTH);
+
+    let offset = derivation.offset(path);
+    apply(model, derivation)?;
+    Some(
+        MutationRecord::new("grammar::replace_subtree")
+            .target(grammar.name(symbol))
+            .offset(offset)
+            .param("node", node),
+    )
+}
+
+// repeats a recursion of the derivation, eg. `(1)` with `<expr> ::= "(" <expr>
+// ")" | "1"` becomes `(((1)))`
+pub fn expand_recursion(
+    rng: &mut SmallRng,
+    model: &mut GrammarModel,
+    grammar: &Grammar,
+) -> Option<MutationRecord> {
+    let mut derivation = derivation(model, grammar)?;
+    let mut recursions = Vec::new();
+    find_recursions(
+        &derivation,
+        &mut Vec::new(),
+        &mut Vec::new(),
+        &mut recursions,
+    );
+    let (outer, inner) = recursions.choose(rng)?.clone();
+
+    // the outer node takes the place of the inner one, `repeats` times
+    let repeats = rng.random_range(1..=MAX_RECURSION_REPEATS);
+    let recursion = derivation.get(&outer)?.clone();
+    let mut expanded = recursion.clone();
+    for _ in 0..repeats {
+        let mut next = recursion.clone();
+        *next.get_mut(&inner)? = expanded;
+        expanded = next;
+    }
+    let symbol = expanded.symbol()?;
+    *derivation.get_mut(&outer)? = expanded;
+
+    let offset = derivation.offset(&outer);
+    apply(model, derivation)?;
+    Some(
+        MutationRecord::new("grammar::expand_recursion")
+            .target(grammar.name(symbol))
+            .offset(offset)
+            .param("repeats", repeats),
+    )
+}
+
+// replaces a nonterminal with one of the same nonterminal from another
+// corpus entry's derivation
+pub fn splice(
+    rng: &mut SmallRng,
+    model: &mut GrammarModel,
+    context: &MutationContext,
+) -> Option<MutationRecord> {
+    let grammar = context.grammar.as_deref()?;
+    let (index, partner) = context.corpus.choose_partner(rng)?;
+    let partner = grammar.parse_cached(&partner.content)?;
+    let mut derivation = derivation(model, grammar)?;
+    let paths = derivation.paths();
+
+    let symbols: HashSet<usize> = paths
+        .iter()
+        .filter_map(|path| derivation.get(path)?.symbol())
+        .collect();
+    let donors: Vec<&Node> = partner
+        .paths()
+        .iter()
+        .filter_map(|path| partner.get(path))
+        .filter(|node| {
+            node.symbol()
+                .is_some_and(|symbol| symbols.contains(&symbol))
+        })
+        .collect();
+    let donor = (*donors.choose(rng)?).clone();
+    let symbol = donor.symbol()?;
+
+    let targets: Vec<&Vec<usize>> = paths
+        .iter()
+        .filter(|path| derivation.get(path).and_then(Node::symbol) == Some(symbol))
+        .collect();
+    let path = (*targets.choose(rng)?).clone();
+    *derivation.get_mut(&path)? = donor;
+
+    let offset = derivation.offset(&path);
+    apply(model, derivation)?;
+    Some(
+        context.corpus.record_partner(
+            MutationRecord::new("grammar::splice")
+                .target(grammar.name(symbol))
+                .offset(offset),
+            index,
+        ),
+    )
+}
+
+// every (node, descendant) pair with the same nonterminal, the descendant's
+// path is relative to the node
+fn find_recursions(
+    node: &Node,
+    path: &mut Vec<usize>,
+    ancestors: &mut Vec<usize>,
+    recursions: &mut Vec<(Vec<usize>, Vec<usize>)>,
+) {
+    let Node::NonTerminal {
+        symbol, children, ..
+    } = node
+    else {
+        return;
+    };
+    for (depth, ancestor) in ancestors.iter().enumerate() {
+        if ancestor == symbol {
+            recursions.push((path[..depth].to_vec(), path[depth..].to_vec()));
+        }
+    }
+    ancestors.push(*symbol);
+    for (i, child) in children.iter().enumerate() {
+        path.push(i);
+        find_recursions(child, path, ancestors, recursions);
+        path.pop();
+    }
+    ancestors.pop();
+}
+
+// a copy of the model's derivation, parsed from its bytes the first time
+fn derivation(model: &mut GrammarModel, grammar: &Grammar) -> Option<Node> {
+    if model.derivation.is_none() {
+        model.derivation = grammar.parse_cached(&model.bytes);
+    }
+    model.derivation.clone()
+}
+
+// mutated derivations that grew too large aren't applied
+fn apply(model: &mut GrammarModel, derivation: Node) -> Option<()> {
+    if derivation.depth() > MAX_DERIVATION_DEPTH || derivation.unparse().len() > MAX_DERIVATION_LEN
+    {
+        return None;
+    }
+    model.derivation = Some(derivation);
+    Some(())
+}
@@ -0,0 +1,429 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use rand::{Rng, rngs::SmallRng, seq::IndexedRandom};
+use serde_json::json;
+
+use crate::{
+    corpus::Corpus,
+    dictionary::{Dictionary, escape},
+    formats::{
+        jpeg::{Jpeg, JpegModel, JpegSegment},
+        template::FileFormat,
+    },
+    mutate::{MutationRecord, mutate_bytes},
+};
+
+// random picks of a non-header byte to flip before flip_data_bits gives up
+const MAX_FLIP_TRIES: usize = 64;
+
+pub fn truncate(model: &mut JpegModel, rng: &mut SmallRng) -> Option<MutationRecord> {
+    if model.segments.is_empty() {
+        return None;
+    }
+
+    let cut = rng.random_range(0..model.segments.len());
+    model.segments.truncate(cut);
+    Some(MutationRecord::new("jpeg::truncate").param("segments", cut))
+}
+
+pub fn remove_eoi(model: &mut JpegModel) -> Option<MutationRecord> {
+    // remove EOI - last 2 bytes are a flag that represent the end
+    // of the jpeg
+    model.eoi.clear();
+    Some(MutationRecord::new("jpeg::remove_eoi").target("EOI"))
+}
+
+pub fn corrupt_soi(model: &mut JpegModel, rng: &mut SmallRng) -> Option<MutationRecord> {
+    if model.soi.len() < 2 {
+        return None;
+    }
+
+    // corrupt SOI - replace the traditional jpeg start flag with a random byte
+    let rand_byte = rng.random::<u8>();
+    model.soi[1] = rand_byte;
+    Some(
+        MutationRecord::new("jpeg::corrupt_soi")
+            .target("SOI")
+            .offset(1)
+            .param("byte", rand_byte),
+    )
+}
+
+pub fn change_dims(model: &mut JpegModel, rng: &mut SmallRng) -> Option<MutationRecord> {
+    // corrupt SOF - change the expected width/height of the file
+    // xFF xC0 corresponds to baseline
+    // xFF xC2 corresponds to progressive
+    let mut dims = Vec::new();
+    for (i, seg) in model.segments.iter_mut().enumerate() {
+        // the marker, length and precision come before the dimensions, a
+        // flipped length can leave a segment too short to hold them
+        if let JpegSegment::Sof(data) = seg
+            && data.len() >= 9
+        {
+            data[5] = rng.random::<u8>();
+            data[6] = rng.random::<u8>();
+            data[7] = rng.random::<u8>();
+            data[8] = rng.random::<u8>();
+            dims.push(json!({
+                "segment": i,
+                "height": u16::from_be_bytes([data[5], data[6]]),
+                "width": u16::from_be_bytes([data[7], data[8]]),
+            }));
+        }
+    }
+    if dims.is_empty() {
+        return None;
+    }
+    Some(
+        MutationRecord::new("jpeg::change_dims")
+            .target("SOF")
+            .param("dims", dims),
+    )
+}
+
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_sign_loss,
+    clippy::cast_precision_loss
+)]
+pub fn flip_data_bits(model: &mut JpegModel, rng: &mut SmallRng) -> Result<Option<MutationRecord>> {
+    if model.segments.is_empty() {
+        return Ok(None);
+    }
+
+    // byteflip non-header data, flip at most 2% of all non-header bytes
+    let mut bytes = Jpeg::generate(model.clone())?;
+    if bytes.len() <= 2 {
+        return Ok(None);
+    }
+    let mutation_rate = rng.random_range(0.001..0.02);
+    let total_byteflip_mutations = (bytes.len() as f64 * mutation_rate).ceil() as usize;
+
+    // first collect all header indicies
+    let mut header_indices = HashSet::new();
+    for i in 0..bytes.len() - 1 {
+        if bytes[i] == 0xFF && bytes[i + 1] != 0x00 {
+            header_indices.insert(i);
+            header_indices.insert(i + 1);
+        }
+    }
+
+    for _ in 0..total_byteflip_mutations {
+        // the input can be nothing but headers
+        let Some(index) = (0..MAX_FLIP_TRIES)
+            .map(|_| rng.random_range(0..bytes.len() - 2))
+            .find(|index| !header_indices.contains(index))
+        else {
+            return Ok(None);
+        };
+        bytes[index] ^= 1 << rng.random_range(0..8);
+    }
+
+    *model = Jpeg::parse(&bytes)?;
+    Ok(Some(
+        MutationRecord::new("jpeg::flip_data_bits")
+            .param("rate", mutation_rate)
+            .param("flips", total_byteflip_mutations),
+    ))
+}
+
+pub fn append_garbage(model: &mut JpegModel, rng: &mut SmallRng) -> Option<MutationRecord> {
+    // add trailing garbage bytes at end
+    let tail_length = rng.random_range(0..10_000);
+    for _ in 0..tail_length {
+        model.eoi.push(rng.random::<u8>());
+    }
+    Some(
+        MutationRecord::new("jpeg::append_garbage")
+            .target("EOI")
+            .param("length", tail_length),
+    )
+}
+
+pub fn change_segment_length(model: &mut JpegModel, rng: &mut SmallRng) -> Option<MutationRecord> {
+    if model.segments.is_empty() {
+        return None;
+    }
+
+    // overwrite segment lengths, the two bytes after the segment header indicate the segment length
+    let random_seg_idx = rng.random_range(..model.segments.len());
+    let segment = &mut model.segments[random_seg_idx];
+
+    let (data, segment_name) = match segment {
+        JpegSegment::App(v) => (v, "APP"),
+        JpegSegment::Dqt(v) => (v, "DQT"),
+        JpegSegment::Sof(v) => (v, "SOF"),
+        JpegSegment::Dht(v) => (v, "DHT"),
+        JpegSegment::Sos(v) => (v, "SOS"),
+        JpegSegment::Dat(v) => (v, "DAT"),
+    };
+    // the length follows the two marker bytes, data segments can be shorter
+    if data.len() < 4 {
+        return None;
+    }
+
+    // generate two bytes from the same rng call
+    let [high_byte, low_byte] = rng.random::<u16>().to_be_bytes();
+    data[2] = high_byte;
+    data[3] = low_byte;
+
+    Some(
+        MutationRecord::new("jpeg::change_segment_length")
+            .target(segment_name)
+            .offset(2)
+            .param("segment", random_seg_idx)
+            .param("length", u16::from_be_bytes([high_byte, low_byte])),
+    )
+}
+
+pub fn swap_segments(model: &mut JpegModel, rng: &mut SmallRng) -> Option<MutationRecord> {
+    // there is nothing to swap with a single segment
+    if model.segments.len() < 2 {
+        return None;
+    }
+
+    // rearrange the segments
+    let mut first = usize::MAX;
+    let mut second = usize::MAX;
+    while first == second {
+        first = rng.random_range(0..model.segments.len());
+        second = rng.random_range(0..model.segments.len());
+    }
+    model.segments.swap(first, second);
+    Some(
+        MutationRecord::new("jpeg::swap_segments")
+            .param("first", first)
+            .param("second", second),
+    )
+}
+
+pub fn mutate_dht(model: &mut JpegModel, rng: &mut SmallRng) -> Option<MutationRecord> {
+    // alter dht tables
+    let mut tables = Vec::new();
+    for (i, seg) in model.segments.iter_mut().enumerate() {
+        if let JpegSegment::Dht(data) = seg
+            && data.len() > 5
+        {
+            let mut table = data.split_off(5);
+            let byte_mutation = mutate_bytes(rng, &mut table);
+            data.extend(table);
+            sync_length(data);
+            tables.push(json!({ "segment": i, "mutation": byte_mutation }));
+        }
+    }
+    if tables.is_empty() {
+        return None;
+    }
+    // offsets of the byte mutations are relative to the table data
+    Some(
+        MutationRecord::new("jpeg::mutate_dht")
+            .target("DHT")
+            .param("tables", tables),
+    )
+}
+
+pub fn mutate_dqt(model: &mut JpegModel, rng: &mut SmallRng) -> Option<MutationRecord> {
+    // alter dqt tables
+    let mut tables = Vec::new();
+    for (i, seg) in model.segments.iter_mut().enumerate() {
+        if let JpegSegment::Dqt(data) = seg
+            && data.len() > 5
+        {
+            let mut table = data.split_off(5);
+            let byte_mutation = mutate_bytes(rng, &mut table);
+            data.extend(table);
+            sync_length(data);
+            tables.push(json!({ "segment": i, "mutation": byte_mutation }));
+        }
+    }
+    if tables.is_empty() {
+        return None;
+    }
+    Some(
+        MutationRecord::new("jpeg::mutate_dqt")
+            .target("DQT")
+            .param("tables", tables),
+    )
+}
+
+// rewrites the length field of a segment after its payload was resized, it
+// counts itself but not the marker
+fn sync_length(data: &mut [u8]) {
+    if data.len() >= 4
+        && let Ok(length) = u16::try_from(data.len() - 2)
+    {
+        data[2..4].copy_from_slice(&length.to_be_bytes());
+    }
+}
+
+// inserts or overwrites a dictionary token in the payload of a segment. the
+// length field is kept in sync when the segment grows so parsers still reach
+// the token
+pub fn dict_segment_data(
+    model: &mut JpegModel,
+    rng: &mut SmallRng,
+    dictionary: &Dictionary,
+) -> Option<MutationRecord> {
+    let token = dictionary.choose(rng)?;
+    let candidates: Vec<usize> = model
+        .segments
+        .iter()
+        .enumerate()
+        .filter(|(_, seg)| !matches!(seg, JpegSegment::Dat(_)))
+        .map(|(i, _)| i)
+        .collect();
+    let seg_idx = *candidates.choose(rng)?;
+
+    let (data, segment_name) = match &mut model.segments[seg_idx] {
+        JpegSegment::App(v) => (v, "APP"),
+        JpegSegment::Dqt(v) => (v, "DQT"),
+        JpegSegment::Sof(v) => (v, "SOF"),
+        JpegSegment::Dht(v) => (v, "DHT"),
+        JpegSegment::Sos(v) => (v, "SOS"),
+        JpegSegment::Dat(_) => return None,
+    };
+    // the marker and length come first
+    if data.len() < 4 {
+        return None;
+    }
+
+    let insert = data.len() - 4 < token.len() || rng.random_bool(0.5);
+    let offset = if insert {
+        let offset = rng.random_range(4..=data.len());
+        data.splice(offset..offset, token.iter().copied());
+        sync_length(data);
+        offset
+    } else {
+        let offset = rng.random_range(4..=data.len() - token.len());
+        data[offset..offset + token.len()].copy_from_slice(token);
+        offset
+    };
+
+    Some(
+        MutationRecord::new("jpeg::dict_segment_data")
+            .target(segment_name)
+            .offset(offset)
+            .param("segment", seg_idx)
+            .param("token", escape(token))
+            .param("mode", if insert { "insert" } else { "overwrite" }),
+    )
+}
+
+// inserts an APPn segment holding a dictionary token before the scan, where
+// metadata like Exif or ICC profiles would sit
+pub fn insert_dict_segment(
+    model: &mut JpegModel,
+    rng: &mut SmallRng,
+    dictionary: &Dictionary,
+) -> Option<MutationRecord> {
+    let token = dictionary.choose(rng)?;
+    let length = u16::try_from(token.len() + 2).ok()?;
+    let marker = 0xE0 + rng.random_range(0..16);
+
+    let mut data = vec![0xFF, marker];
+    data.extend_from_slice(&length.to_be_bytes());
+    data.extend_from_slice(token);
+
+    let scan = model
+        .segments
+        .iter()
+        .position(|seg| matches!(seg, JpegSegment::Sos(_)))
+        .unwrap_or(model.segments.len());
+    let index = rng.random_range(0..=scan);
+    model.segments.insert(index, JpegSegment::App(data));
+
+    Some(
+        MutationRecord::new("jpeg::insert_dict_segment")
+            .target(format!("APP{}", marker - 0xE0))
+            .param("segment", index)
+            .param("token", escape(token)),
+    )
+}
+
+// replaces a DQT or DHT segment with one of the same kind from another corpus
+// entry
+pub fn swap_tables(
+    model: &mut JpegModel,
+    rng: &mut SmallRng,
+    corpus: &Corpus,
+) -> Option<MutationRecord> {
+    let (index, partner) = corpus.choose_partner(rng)?;
+    let partner = Jpeg::parse(&partner.content).ok()?;
+
+    let tables: Vec<usize> = model
+        .segments
+        .iter()
+        .enumerate()
+        .filter(|(_, seg)| matches!(seg, JpegSegment::Dqt(_) | JpegSegment::Dht(_)))
+        .map(|(i, _)| i)
+        .collect();
+    let seg_idx = *tables.choose(rng)?;
+    let is_dqt = matches!(model.segments[seg_idx], JpegSegment::Dqt(_));
+
+    let replacements: Vec<(usize, &JpegSegment)> = partner
+        .segments
+        .iter()
+        .enumerate()
+        .filter(|(_, seg)| match seg {
+            JpegSegment::Dqt(_) => is_dqt,
+            JpegSegment::Dht(_) => !is_dqt,
+            _ => false,
+        })
+        .collect();
+    let (from, replacement) = *replacements.choose(rng)?;
+    model.segments[seg_idx] = replacement.clone();
+
+    Some(
+        corpus.record_partner(
+            MutationRecord::new("jpeg::swap_tables")
+                .target(if is_dqt { "DQT" } else { "DHT" })
+                .param("segment", seg_idx)
+                .param("from", from),
+            index,
+        ),
+    )
+}
+
+// inserts a header segment of another corpus entry before the scan
+pub fn transplant_segment(
+    model: &mut JpegModel,
+    rng: &mut SmallRng,
+    corpus: &Corpus,
+) -> Option<MutationRecord> {
+    let (index, partner) = corpus.choose_partner(rng)?;
+    let partner = Jpeg::parse(&partner.content).ok()?;
+
+    let candidates: Vec<(usize, &JpegSegment)> = partner
+        .segments
+        .iter()
+        .enumerate()
+        .filter(|(_, seg)| !matches!(seg, JpegSegment::Sos(_) | JpegSegment::Dat(_)))
+        .collect();
+    let (from, segment) = *candidates.choose(rng)?;
+    let segment_name = match segment {
+        JpegSegment::App(_) => "APP",
+        JpegSegment::Dqt(_) => "DQT",
+        JpegSegment::Sof(_) => "SOF",
+        JpegSegment::Dht(_) => "DHT",
+        JpegSegment::Sos(_) | JpegSegment::Dat(_) => return None,
+    };
+
+    let scan = model
+        .segments
+        .iter()
+        .position(|seg| matches!(seg, JpegSegment::Sos(_)))
+        .unwrap_or(model.segments.len());
+    let at = rng.random_range(0..=scan);
+    model.segments.insert(at, segment.clone());
+
+    Some(
+        corpus.record_partner(
+            MutationRecord::new("jpeg::transplant_segment")
+                .target(segment_name)
+                .param("segment", at)
+                .param("from", from),
+            index,
+        ),
+    )
+}
@@ -1 +1,183 @@
+pub mod bytes;
+pub mod cmplog;
+pub mod deterministic;
+pub mod external;
+pub mod grammar;
+pub mod jpeg;
 pub mod png;
+pub mod schedule;
+
+use std::rc::Rc;
+
+use anyhow::{Result, anyhow};
+use rand::{SeedableRng, rngs::SmallRng};
+use serde::Serialize;
+
+use crate::{
+    corpus::Corpus, dictionary::Dictionary, external::ExternalMutator, grammar::Grammar,
+    mutate::MutationRecord,
+};
+
+// A single kind of mutation for a model. Any function or closure with the
+// right signature is a mutator. Returning None means the mutation couldn't
+// be applied to this model (eg. the chunk it changes is missing).
+pub trait Mutator<M> {
+    fn mutate(&self, rng: &mut SmallRng, model: &mut M) -> Result<Option<MutationRecord>>;
+}
+
+impl<M, F> Mutator<M> for F
+where
+    F: Fn(&mut SmallRng, &mut M) -> Result<Option<MutationRecord>>,
+{
+    fn mutate(&self, rng: &mut SmallRng, model: &mut M) -> Result<Option<MutationRecord>> {
+        self(rng, model)
+    }
+}
+
+// What mutators can draw on besides the model and their rng: tokens for the
+// dictionary mutators, other corpus entries for the splice mutators, the
+// external mutator processes and the grammar of the grammar fuzz type
+#[derive(Default)]
+pub struct MutationContext {
+    pub dictionary: Dictionary,
+    pub corpus: Corpus,
+    pub external: Vec<Rc<ExternalMutator>>,
+    pub grammar: Option<Rc<Grammar>>,
+}
+
+pub struct RegisteredMutator<M> {
+    pub name: String,
+    pub weight: u32,
+    mutator: Box<dyn Mutator<M>>,
+}
+
+impl<M> RegisteredMutator<M> {
+    // applies the mutation with its own rng seeded from `rng_seed`, so the
+    // mutation only depends on the model and the seed stored in its record
+    pub fn apply(&self, model: &mut M, rng_seed: u64) -> Result<Option<MutationRecord>> {
+        let mut rng = SmallRng::seed_from_u64(rng_seed);
+        let mutation = self.mutator.mutate(&mut rng, model)?;
+        Ok(mutation.map(|mut mutation| {
+            debug_assert_eq!(
+                mutation.mutator, self.name,
+                "records are named after their mutator"
+            );
+            mutation.rng_seed = rng_seed;
+            mutation
+        }))
+    }
+}
+
+// Every mutator a format supports, with the weight it is picked with. Names
+// are `<format>::<mutation>` and match the `mutator` of the records they
+// produce, so a record can be re-applied by looking its mutator up.
+pub struct MutatorRegistry<M> {
+    mutators: Vec<RegisteredMutator<M>>,
+}
+
+impl<M> MutatorRegistry<M> {
+    pub fn new() -> Self {
+        Self {
+            mutators: Vec::new(),
+        }
+    }
+
+    pub fn register(
+        mut self,
+        name: impl Into<String>,
+        weight: u32,
+        mutator: impl Mutator<M> + 'static,
+    ) -> Self {
+        self.mutators.push(RegisteredMutator {
+            name: name.into(),
+            weight,
+            mutator: Box::new(mutator),
+        });
+        self
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RegisteredMutator<M>> {
+        self.mutators.iter().find(|mutator| mutator.name == name)
+    }
+
+    // in registration order, the scheduler picks from these by index
+    pub fn mutators(&self) -> &[RegisteredMutator<M>] {
+        &self.mutators
+    }
+
+    // applies the user's selection on top of the default weights. every
+    // pattern has to match at least one mutator so typos don't go unnoticed
+    pub fn configure(mut self, selection: &MutatorSelection) -> Result<Self> {
+        for pattern in selection.enabled.iter().chain(&selection.disabled) {
+            if !self.mutators.iter().any(|m| pattern.matches(&m.name)) {
+                let names: Vec<&str> = self.mutators.iter().map(|m| m.name.as_str()).collect();
+                return Err(anyhow!(
+                    "`{}` doesn't match any mutator, available mutators are: {}",
+                    pattern.pattern,
+                    names.join(", ")
+                ));
+            }
+        }
+
+        for mutator in &mut self.mutators {
+            if !selection.enabled.is_empty() {
+                mutator.weight = match selection.enabled.iter().find(|p| p.matches(&mutator.name)) {
+                    Some(pattern) => pattern.weight.unwrap_or(mutator.weight),
+                    None => 0,
+                };
+            }
+            if selection.disabled.iter().any(|p| p.matches(&mutator.name)) {
+                mutator.weight = 0;
+            }
+        }
+
+        if self.mutators.iter().all(|mutator| mutator.weight == 0) {
+            return Err(anyhow!("every mutator is disabled"));
+        }
+        Ok(self)
+    }
+}
+
+// A mutator name, or a prefix followed by `*` (eg. `png::change_*`),
+// optionally with a weight overriding the default one
+#[derive(Serialize, Debug, Clone)]
+pub struct MutatorPattern {
+    pub pattern: String,
+    pub weight: Option<u32>,
+}
+
+impl MutatorPattern {
+    // parses `name` or `name=weight`
+    pub fn parse(s: &str) -> Result<Self> {
+        let (pattern, weight) = match s.split_once('=') {
+            Some((pattern, weight)) => (
+                pattern,
+                Some(
+                    weight
+                        .parse()
+                        .map_err(|_| anyhow!("invalid weight for mutator {pattern}: {weight}"))?,
+                ),
+            ),
+            None => (s, None),
+        };
+        Ok(Self {
+            pattern: pattern.trim().to_string(),
+            weight,
+        })
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => name.starts_with(prefix),
+            None => name == self.pattern,
+        }
+    }
+}
+
+// Which mutators a campaign uses. When `enabled` is empty every mutator is
+// used with its default weight.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct MutatorSelection {
+    pub enabled: Vec<MutatorPattern>,
+    pub disabled: Vec<MutatorPattern>,
+}
@@ -1,26 +1,38 @@
-use rand::{
-    Rng,
-    seq::{IndexedMutRandom, IndexedRandom},
-};
+use rand::{Rng, rngs::SmallRng, seq::IndexedRandom};
 
-use crate::formats::png::{Chunk, PngModel};
+use crate::{
+    corpus::Corpus,
+    dictionary::{Dictionary, escape},
+    formats::{
+        png::{Chunk, Png, PngModel, RawChunk, png_crc},
+        template::FileFormat,
+    },
+    mutate::MutationRecord,
+};
 
-pub fn remove_ihdr(model: &mut PngModel) -> String {
+// the remove_* mutators do nothing, and aren't recorded, without the chunk
+pub fn remove_ihdr(model: &mut PngModel) -> Option<MutationRecord> {
+    let len = model.chunks.len();
     model.chunks.retain(|c| !matches!(c, Chunk::Ihdr(..)));
-    "removed ihdr".to_string()
+    (model.chunks.len() < len).then(|| MutationRecord::new("png::remove_ihdr").target("IHDR"))
 }
 
-pub fn remove_idat(model: &mut PngModel) -> String {
+pub fn remove_idat(model: &mut PngModel) -> Option<MutationRecord> {
+    let len = model.chunks.len();
     model.chunks.retain(|c| !matches!(c, Chunk::Idat(..)));
-    "removed idat".to_string()
+    (model.chunks.len() < len).then(|| MutationRecord::new("png::remove_idat").target("IDAT"))
 }
 
-pub fn remove_iend(model: &mut PngModel) -> String {
+pub fn remove_iend(model: &mut PngModel) -> Option<MutationRecord> {
+    let len = model.chunks.len();
     model.chunks.retain(|c| !matches!(c, Chunk::Iend(_)));
-    "removed iend".to_string()
+    (model.chunks.len() < len).then(|| MutationRecord::new("png::remove_iend").target("IEND"))
 }
 
-pub fn change_png_dims(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) -> String {
+pub fn change_png_dims(
+    model: &mut PngModel,
+    rng: &mut rand::prelude::SmallRng,
+) -> Option<MutationRecord> {
     let possibilities = &[
         0,
         1,
@@ -62,14 +74,18 @@ pub fn change_png_dims(model: &mut PngModel, rng: &mut rand::prelude::SmallRng)
         }
     }
 
-    if changed {
-        format!("changed width/height to {rand_width}/{rand_height}").to_string()
-    } else {
-        "could not find ihdr".to_string()
-    }
+    changed.then(|| {
+        MutationRecord::new("png::change_dims")
+            .target("IHDR")
+            .param("width", *rand_width)
+            .param("height", *rand_height)
+    })
 }
 
-pub fn change_depth(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) -> String {
+pub fn change_depth(
+    model: &mut PngModel,
+    rng: &mut rand::prelude::SmallRng,
+) -> Option<MutationRecord> {
     // 0 and 128 are not supported under any circumstances, the rest are possible
     let possibilities = &[0, 1, 2, 4, 8, 16, 32, 64, 128];
     let rand_depth = possibilities.choose(rng).unwrap_or(&1u8);
@@ -82,14 +98,17 @@ pub fn change_depth(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) ->
         }
     }
 
-    if changed {
-        format!("changed depth to {rand_depth}").to_string()
-    } else {
-        "could not find ihdr".to_string()
-    }
+    changed.then(|| {
+        MutationRecord::new("png::change_depth")
+            .target("IHDR.depth")
+            .param("depth", *rand_depth)
+    })
 }
 
-pub fn change_color_type(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) -> String {
+pub fn change_color_type(
+    model: &mut PngModel,
+    rng: &mut rand::prelude::SmallRng,
+) -> Option<MutationRecord> {
     // the only valid color types are 0, 2, 3, 4, and 6
     let possibilities = &[0, 1, 2, 3, 4, 6, 8, 32, u8::MAX - 1, u8::MAX];
     let rand_ctype = possibilities.choose(rng).unwrap_or(&1u8);
@@ -102,53 +121,59 @@ pub fn change_color_type(model: &mut PngModel, rng: &mut rand::prelude::SmallRng
         }
     }
 
-    if changed {
-        format!("changed color type to {rand_ctype}").to_string()
-    } else {
-        "could not find ihdr".to_string()
-    }
+    changed.then(|| {
+        MutationRecord::new("png::change_color_type")
+            .target("IHDR.color_type")
+            .param("color_type", *rand_ctype)
+    })
 }
 
 pub fn change_compression_method(
     model: &mut PngModel,
     rng: &mut rand::prelude::SmallRng,
-) -> String {
+) -> Option<MutationRecord> {
     // the only valid integer is zero, so replace it with any other u8
-    let rand_u8 = rng.random::<u8>();
+    let rand_u8 = rng.random_range(1..=u8::MAX);
     let mut changed = false;
     for chunk in &mut model.chunks {
         if let Chunk::Ihdr(ihdr, _) = chunk {
-            ihdr.color_type = rng.random::<u8>();
+            ihdr.compression_method = rand_u8;
             changed = true;
         }
     }
 
-    if changed {
-        format!("changed comperssion method to a nonzero integer {rand_u8}").to_string()
-    } else {
-        "could not find ihdr".to_string()
-    }
+    changed.then(|| {
+        MutationRecord::new("png::change_compression_method")
+            .target("IHDR.compression_method")
+            .param("compression_method", rand_u8)
+    })
 }
 
-pub fn change_filter_method(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) -> String {
+pub fn change_filter_method(
+    model: &mut PngModel,
+    rng: &mut rand::prelude::SmallRng,
+) -> Option<MutationRecord> {
     // the only valid integer is zero, so replace it with any other u8
-    let rand_u8 = rng.random::<u8>();
+    let rand_u8 = rng.random_range(1..=u8::MAX);
     let mut changed = false;
     for chunk in &mut model.chunks {
         if let Chunk::Ihdr(ihdr, _) = chunk {
-            ihdr.filter_method = rng.random::<u8>();
+            ihdr.filter_method = rand_u8;
             changed = true;
         }
     }
 
-    if changed {
-        format!("changed filter method to a nonzero integer {rand_u8}").to_string()
-    } else {
-        "could not find ihdr".to_string()
-    }
+    changed.then(|| {
+        MutationRecord::new("png::change_filter_method")
+            .target("IHDR.filter_method")
+            .param("filter_method", rand_u8)
+    })
 }
 
-pub fn change_interlace_method(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) -> String {
+pub fn change_interlace_method(
+    model: &mut PngModel,
+    rng: &mut rand::prelude::SmallRng,
+) -> Option<MutationRecord> {
     // the only valid interlace methods are 0 and 1
     let possibilities = &[0, 1, 2, 3, u8::MAX];
     let rand_interlace_method = possibilities.choose(rng).unwrap_or(&0u8);
@@ -161,17 +186,21 @@ pub fn change_interlace_method(model: &mut PngModel, rng: &mut rand::prelude::Sm
         }
     }
 
-    if changed {
-        format!("changed color type to {rand_interlace_method}").to_string()
-    } else {
-        "could not find ihdr".to_string()
-    }
+    changed.then(|| {
+        MutationRecord::new("png::change_interlace_method")
+            .target("IHDR.interlace_method")
+            .param("interlace_method", *rand_interlace_method)
+    })
 }
 
-pub fn xor_crc(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) -> String {
+pub fn xor_crc(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) -> Option<MutationRecord> {
     let rand_pow = 1 << rng.random_range(0..32);
-    let chunk = model.chunks.choose_mut(rng).unwrap();
-    let chunk_type = match chunk {
+    if model.chunks.is_empty() {
+        return None;
+    }
+
+    let chunk_index = rng.random_range(0..model.chunks.len());
+    let chunk_type = match &mut model.chunks[chunk_index] {
         Chunk::Ihdr(_, crc) => {
             crc.crc ^= rand_pow;
             "IHDR"
@@ -186,16 +215,25 @@ pub fn xor_crc(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) -> Strin
         }
         Chunk::Ancillary(raw) => {
             raw.crc ^= rand_pow;
-            &String::from_utf8(raw.chunk_type.to_vec()).unwrap()
+            &String::from_utf8_lossy(&raw.chunk_type).to_string()
         }
     };
 
-    format!("XOR crc of {chunk_type} by {rand_pow}")
+    Some(
+        MutationRecord::new("png::xor_crc")
+            .target(format!("{chunk_type}.crc"))
+            .param("chunk", chunk_index)
+            .param("mask", rand_pow),
+    )
 }
 
-pub fn zero_crc(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) -> String {
-    let chunk = model.chunks.choose_mut(rng).unwrap();
-    let chunk_type = match chunk {
+pub fn zero_crc(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) -> Option<MutationRecord> {
+    if model.chunks.is_empty() {
+        return None;
+    }
+
+    let chunk_index = rng.random_range(0..model.chunks.len());
+    let chunk_type = match &mut model.chunks[chunk_index] {
         Chunk::Ihdr(_, crc) => {
             crc.crc = 0;
             "IHDR"
@@ -210,9 +248,158 @@ pub fn zero_crc(model: &mut PngModel, rng: &mut rand::prelude::SmallRng) -> Stri
         }
         Chunk::Ancillary(raw) => {
             raw.crc = 0;
-            &String::from_utf8(raw.chunk_type.to_vec()).unwrap()
+            &String::from_utf8_lossy(&raw.chunk_type).to_string()
         }
     };
 
-    format!("zeroed the crc of {chunk_type}")
+    Some(
+        MutationRecord::new("png::zero_crc")
+            .target(format!("{chunk_type}.crc"))
+            .param("chunk", chunk_index),
+    )
+}
+
+// inserts an ancillary chunk whose type is a 4 byte dictionary token, holding
+// another token or nothing. the crc is valid so the chunk isn't rejected early
+pub fn insert_dict_chunk(
+    model: &mut PngModel,
+    rng: &mut SmallRng,
+    dictionary: &Dictionary,
+) -> Option<MutationRecord> {
+    let chunk_type: [u8; 4] = dictionary.choose_len(rng, 4)?.try_into().ok()?;
+    let data = if rng.random_bool(0.5) {
+        dictionary.choose(rng)?.to_vec()
+    } else {
+        Vec::new()
+    };
+
+    // keep IHDR first
+    let index = rng.random_range(model.chunks.len().min(1)..=model.chunks.len());
+    let record = MutationRecord::new("png::insert_dict_chunk")
+        .target(String::from_utf8_lossy(&chunk_type).to_string())
+        .param("chunk", index)
+        .param("data", escape(&data));
+    model.chunks.insert(
+        index,
+        Chunk::Ancillary(RawChunk {
+            length: u32::try_from(data.len()).ok()?,
+            chunk_type,
+            crc: png_crc(&chunk_type, &data),
+            data,
+        }),
+    );
+    Some(record)
+}
+
+// inserts or overwrites a dictionary token in the data of an IDAT or ancillary
+// chunk, then fixes up the crc
+pub fn dict_chunk_data(
+    model: &mut PngModel,
+    rng: &mut SmallRng,
+    dictionary: &Dictionary,
+) -> Option<MutationRecord> {
+    let token = dictionary.choose(rng)?;
+    let candidates: Vec<usize> = model
+        .chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| matches!(chunk, Chunk::Idat(..) | Chunk::Ancillary(_)))
+        .map(|(i, _)| i)
+        .collect();
+    let chunk_index = *candidates.choose(rng)?;
+
+    let (chunk_type, data) = match &mut model.chunks[chunk_index] {
+        Chunk::Idat(data, _) => (*b"IDAT", data),
+        Chunk::Ancillary(raw) => (raw.chunk_type, &mut raw.data),
+        Chunk::Ihdr(..) | Chunk::Iend(_) => return None,
+    };
+
+    let insert = data.len() < token.len() || rng.random_bool(0.5);
+    let offset = if insert {
+        let offset = rng.random_range(0..=data.len());
+        data.splice(offset..offset, token.iter().copied());
+        offset
+    } else {
+        let offset = rng.random_range(0..=data.len() - token.len());
+        data[offset..offset + token.len()].copy_from_slice(token);
+        offset
+    };
+
+    let crc = png_crc(&chunk_type, data);
+    match &mut model.chunks[chunk_index] {
+        Chunk::Idat(_, chunk_crc) => chunk_crc.crc = crc,
+        Chunk::Ancillary(raw) => {
+            raw.crc = crc;
+            raw.length = u32::try_from(raw.data.len()).ok()?;
+        }
+        Chunk::Ihdr(..) | Chunk::Iend(_) => {}
+    }
+
+    Some(
+        MutationRecord::new("png::dict_chunk_data")
+            .target(String::from_utf8_lossy(&chunk_type).to_string())
+            .offset(offset)
+            .param("chunk", chunk_index)
+            .param("token", escape(token))
+            .param("mode", if insert { "insert" } else { "overwrite" }),
+    )
+}
+
+// copies an IDAT or ancillary chunk of another corpus entry into the model
+pub fn transplant_chunk(
+    model: &mut PngModel,
+    rng: &mut SmallRng,
+    corpus: &Corpus,
+) -> Option<MutationRecord> {
+    let (index, partner) = corpus.choose_partner(rng)?;
+    let partner = Png::parse(&partner.content).ok()?;
+    let candidates: Vec<(usize, &Chunk)> = partner
+        .chunks
+        .iter()
+        .enumerate()
+        .filter(|(_, chunk)| matches!(chunk, Chunk::Idat(..) | Chunk::Ancillary(_)))
+        .collect();
+    let (from, chunk) = *candidates.choose(rng)?;
+    let chunk_type = match chunk {
+        Chunk::Ancillary(raw) => String::from_utf8_lossy(&raw.chunk_type).to_string(),
+        _ => "IDAT".to_string(),
+    };
+
+    // keep IHDR first
+    let at = rng.random_range(model.chunks.len().min(1)..=model.chunks.len());
+    model.chunks.insert(at, chunk.clone());
+    Some(
+        corpus.record_partner(
+            MutationRecord::new("png::transplant_chunk")
+                .target(chunk_type)
+                .param("from", from)
+                .param("chunk", at),
+            index,
+        ),
+    )
+}
+
+// replaces the IHDR with the one of another corpus entry, so the image data
+// no longer matches the header describing it
+pub fn swap_ihdr(
+    model: &mut PngModel,
+    rng: &mut SmallRng,
+    corpus: &Corpus,
+) -> Option<MutationRecord> {
+    let (index, partner) = corpus.choose_partner(rng)?;
+    let partner = Png::parse(&partner.content).ok()?;
+    let partner_ihdr = partner
+        .chunks
+        .into_iter()
+        .find(|chunk| matches!(chunk, Chunk::Ihdr(..)))?;
+
+    let ihdr = model
+        .chunks
+        .iter_mut()
+        .find(|chunk| matches!(chunk, Chunk::Ihdr(..)))?;
+    if *ihdr == partner_ihdr {
+        return None;
+    }
+    *ihdr = partner_ihdr;
+    Some(corpus.record_partner(MutationRecord::new("png::swap_ihdr").target("IHDR"), index))
 }
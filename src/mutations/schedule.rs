@@ -0,0 +1,199 @@
+use std::collections::HashSet;
+
+use rand::{Rng, rngs::SmallRng};
+use serde::Serialize;
+
+use crate::mutations::MutatorRegistry;
+
+// inputs between two updates of the selection probabilities
+const PERIOD: u64 = 500;
+// a new crash signature or exit status is worth this many crashes
+const NEW_BEHAVIOUR_CREDIT: f64 = 10.0;
+// how much of its velocity a particle keeps between periods
+const INERTIA: f64 = 0.7;
+// enabled mutators never drop below this share of a uniform probability, so
+// a mutator that did nothing early on still gets another chance
+const MIN_SHARE: f64 = 0.25;
+
+// How a mutator did over the run, one row of the report's effectiveness table
+#[derive(Serialize, Debug, Clone)]
+pub struct MutatorStats {
+    pub name: String,
+    pub executions: u64, // inputs the mutator was part of
+    pub crashes: u64,
+    pub new_behaviours: u64, // crash signatures or exit statuses not seen before
+    pub probability: f64,    // chance of being picked at the end of the run
+}
+
+// A mutator's selection probability seen as a particle, like MOpt. It is
+// pulled towards the probability it did best at and towards its share of the
+// period's findings.
+struct Particle {
+    position: f64,
+    velocity: f64,
+    best_position: f64,
+    best_efficiency: f64,
+    period_executions: u64,
+    period_credit: f64,
+}
+
+// Picks mutators and, when adaptive, moves the selection probabilities towards
+// the mutators that find crashes and new behaviours
+pub struct Scheduler {
+    adaptive: bool,
+    stats: Vec<MutatorStats>,
+    particles: Vec<Particle>,
+    seen: HashSet<String>,
+    period_inputs: u64,
+}
+
+impl Scheduler {
+    // starts from the registry's configured weights, disabled mutators stay
+    // disabled
+    pub fn new<M>(registry: &MutatorRegistry<M>, adaptive: bool) -> Self {
+        let total: u32 = registry.mutators().iter().map(|m| m.weight).sum();
+        let particles = registry
+            .mutators()
+            .iter()
+            .map(|mutator| {
+                let position = f64::from(mutator.weight) / f64::from(total.max(1));
+                Particle {
+                    position,
+                    velocity: 0.0,
+                    best_position: position,
+                    best_efficiency: 0.0,
+                    period_executions: 0,
+                    period_credit: 0.0,
+                }
+            })
+            .collect();
+        let stats = registry
+            .mutators()
+            .iter()
+            .map(|mutator| MutatorStats {
+                name: mutator.name.clone(),
+                executions: 0,
+                crashes: 0,
+                new_behaviours: 0,
+                probability: 0.0,
+            })
+            .collect();
+
+        Self {
+            adaptive,
+            stats,
+            particles,
+            seen: HashSet::new(),
+            period_inputs: 0,
+        }
+    }
+
+    // index of the mutator to apply next, in registry order
+    pub fn choose(&self, rng: &mut SmallRng) -> usize {
+        let total: f64 = self.particles.iter().map(|p| p.position).sum();
+        let mut pick = rng.random_range(0.0..total);
+        for (i, particle) in self.particles.iter().enumerate() {
+            if pick < particle.position {
+                return i;
+            }
+            pick -= particle.position;
+        }
+        // rounding can leave a sliver past the last enabled mutator
+        self.particles
+            .iter()
+            .rposition(|p| p.position > 0.0)
+            .unwrap_or_default()
+    }
+
+    // credits the mutators an input was made with. `outcome` identifies what
+    // the target did (eg. the crash signature) to spot new behaviours
+    pub fn record(&mut self, rng: &mut SmallRng, applied: &[usize], crashed: bool, outcome: &str) {
+        let new_behaviour = self.seen.insert(outcome.to_string());
+        let credit = if new_behaviour {
+            NEW_BEHAVIOUR_CREDIT
+        } else {
+            f64::from(u8::from(crashed))
+        };
+
+        for &i in applied {
+            let stats = &mut self.stats[i];
+            stats.executions += 1;
+            stats.crashes += u64::from(crashed);
+            stats.new_behaviours += u64::from(new_behaviour);
+
+            let particle = &mut self.particles[i];
+            particle.period_executions += 1;
+            particle.period_credit += credit;
+        }
+
+        self.period_inputs += 1;
+        if self.adaptive && self.period_inputs >= PERIOD {
+            self.update(rng);
+            self.period_inputs = 0;
+        }
+    }
+
+    #[allow(clippy::cast_precision_loss)]
+    fn update(&mut self, rng: &mut SmallRng) {
+        let efficiencies: Vec<f64> = self
+            .particles
+            .iter()
+            .map(|p| {
+                if p.period_executions == 0 {
+                    0.0
+                } else {
+                    p.period_credit / p.period_executions as f64
+                }
+            })
+            .collect();
+        let total_efficiency: f64 = efficiencies.iter().sum();
+        let enabled = self.particles.iter().filter(|p| p.position > 0.0).count();
+
+        // nothing was found this period, so there's nothing to learn from
+        if total_efficiency > 0.0 && enabled > 0 {
+            let min_position = MIN_SHARE / enabled as f64;
+            for (particle, efficiency) in self.particles.iter_mut().zip(&efficiencies) {
+                if particle.position == 0.0 {
+                    continue;
+                }
+                if *efficiency > particle.best_efficiency {
+                    particle.best_efficiency = *efficiency;
+                    particle.best_position = particle.position;
+                }
+
+                let global_best = efficiency / total_efficiency;
+                particle.velocity = INERTIA * particle.velocity
+                    + rng.random::<f64>() * (particle.best_position - particle.position)
+                    + rng.random::<f64>() * (global_best - particle.position);
+                particle.position =
+                    (particle.position + particle.velocity).clamp(min_position, 1.0);
+            }
+
+            let total: f64 = self.particles.iter().map(|p| p.position).sum();
+            for particle in &mut self.particles {
+                particle.position /= total;
+            }
+        }
+
+        for particle in &mut self.particles {
+            particle.period_executions = 0;
+            particle.period_credit = 0.0;
+        }
+    }
+
+    // the effectiveness table, most effective mutators first and the most
+    // exercised first among equally effective ones
+    pub fn stats(&self) -> Vec<MutatorStats> {
+        let total: f64 = self.particles.iter().map(|p| p.position).sum();
+        let mut stats = self.stats.clone();
+        for (stats, particle) in stats.iter_mut().zip(&self.particles) {
+            stats.probability = particle.position / total;
+        }
+        stats.sort_by(|a, b| {
+            (b.new_behaviours, b.crashes)
+                .cmp(&(a.new_behaviours, a.crashes))
+                .then(b.executions.cmp(&a.executions))
+        });
+        stats
+    }
+}
@@ -0,0 +1,99 @@
+use std::fs;
+
+use anyhow::{Result, anyhow};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+// report folders and stats anomie already uses, signals are named SIG*
+const RESERVED_NAMES: [&str; 6] = ["seeds", "OOM", "PANIC", "SANITIZER", "TIMEOUT", "UNKNOWN"];
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OracleAction {
+    Finding,  // record the input under the rule's name
+    Expected, // the target rejected the input as intended, a pass unless it panicked or a sanitizer reported
+}
+
+// A user defined rule deciding what a normal exit means. A rule matches when
+// every condition it sets holds: the exit code is listed in `exit_codes` or is
+// at least `min_exit_code`, and stderr matches the `stderr` regex.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct OracleRule {
+    pub name: String,
+    #[serde(default)]
+    pub exit_codes: Vec<i32>,
+    #[serde(default)]
+    pub min_exit_code: Option<i32>,
+    #[serde(default)]
+    pub stderr: Option<String>,
+    pub action: OracleAction,
+
+    #[serde(skip)]
+    stderr_regex: Option<Regex>,
+}
+
+#[derive(Deserialize)]
+struct OracleFile {
+    rules: Vec<OracleRule>,
+}
+
+impl OracleRule {
+    fn matches(&self, code: i32, stderr: &str) -> bool {
+        let code_set = !self.exit_codes.is_empty() || self.min_exit_code.is_some();
+        let code_matches =
+            self.exit_codes.contains(&code) || self.min_exit_code.is_some_and(|min| code >= min);
+        let stderr_matches = self
+            .stderr_regex
+            .as_ref()
+            .is_none_or(|regex| regex.is_match(stderr));
+
+        (!code_set || code_matches) && stderr_matches
+    }
+}
+
+// reads the rules from a json file, eg.
+// {"rules": [{"name": "BAD_EXIT", "exit_codes": [2], "min_exit_code": 128, "action": "finding"}]}
+pub fn load(path: &str) -> Result<Vec<OracleRule>> {
+    let contents =
+        fs::read_to_string(path).map_err(|e| anyhow!("can't read oracle file {path}: {e}"))?;
+    let mut file: OracleFile =
+        serde_json::from_str(&contents).map_err(|e| anyhow!("invalid oracle file {path}: {e}"))?;
+
+    for rule in &mut file.rules {
+        // rule names become report folders
+        if rule.name.is_empty()
+            || !rule
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(anyhow!(
+                "oracle rule name `{}` must be alphanumeric, '_' or '-'",
+                rule.name
+            ));
+        }
+        if RESERVED_NAMES
+            .iter()
+            .any(|name| rule.name.eq_ignore_ascii_case(name))
+            || rule.name.starts_with("SIG")
+        {
+            return Err(anyhow!(
+                "oracle rule name `{}` clashes with anomie's own crash categories",
+                rule.name
+            ));
+        }
+        if let Some(pattern) = &rule.stderr {
+            rule.stderr_regex = Some(
+                Regex::new(pattern)
+                    .map_err(|e| anyhow!("invalid stderr pattern in rule {}: {e}", rule.name))?,
+            );
+        }
+    }
+
+    Ok(file.rules)
+}
+
+// returns the first rule matching a normal exit, rules are checked in file order
+pub fn evaluate<'a>(rules: &'a [OracleRule], code: i32, stderr: &str) -> Option<&'a OracleRule> {
+    rules.iter().find(|rule| rule.matches(code, stderr))
+}
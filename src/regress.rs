@@ -0,0 +1,199 @@
+use anyhow::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    analysis::{Crash, crash_category},
+    config::RegressArgs,
+    debugger,
+    target::{TargetOutput, run_target_file, run_target_string},
+    triage,
+    types::{Config, FuzzType},
+    utils,
+};
+
+// the parts of a previous run's crashes.json needed to replay it
+#[derive(Deserialize)]
+pub struct RecordedCrashes {
+    pub crashes: Vec<Crash>,
+}
+
+// the parts of a previous run's config.json needed to replay it
+#[derive(Deserialize)]
+pub struct RecordedConfig {
+    pub validated_fuzz_type: FuzzType,
+    #[serde(default)]
+    pub external_mutators: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct RegressionResult {
+    pub report: String,
+    pub file: String,
+    pub old_signature: String,
+    pub new_signature: Option<String>,
+}
+
+// Outcome of replaying every crash, a crash is "changed" when it still
+// crashes but lands in a different bucket than it did originally
+#[derive(Serialize, Default)]
+pub struct RegressionSummary {
+    pub still_crashing: Vec<RegressionResult>,
+    pub changed: Vec<RegressionResult>,
+    pub fixed: Vec<RegressionResult>,
+}
+
+pub fn run_regress(args: &RegressArgs) -> Result<()> {
+    let config = args.target.validate_target()?;
+    utils::create_temp_dirs(&config)?;
+
+    let mut summary = RegressionSummary::default();
+    for report in &args.reports {
+        info!("Replaying crashes from {report}");
+        regress_report(&config, Path::new(report), &mut summary)?;
+    }
+
+    fs::write(&args.json, serde_json::to_string(&summary)?)?;
+    print_summary(&summary, &args.json)?;
+    Ok(())
+}
+
+fn regress_report(
+    config: &Config,
+    report_dir: &Path,
+    summary: &mut RegressionSummary,
+) -> Result<()> {
+    let recorded_crashes: RecordedCrashes =
+        serde_json::from_str(&fs::read_to_string(report_dir.join("crashes.json"))?)?;
+    let recorded_config: RecordedConfig =
+        serde_json::from_str(&fs::read_to_string(report_dir.join("config.json"))?)?;
+
+    for crash in recorded_crashes.crashes {
+        let path = locate_crash_file(report_dir, &crash);
+        let bytes = match fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("Skipping {}: {e}", path.display());
+                continue;
+            }
+        };
+
+        let output = replay(config, &recorded_config.validated_fuzz_type, &path, &bytes)?;
+        let new_signature = match crash_category(&output, &config.oracles) {
+            Some(category) => {
+                let mut triage = triage::classify(&output);
+                // mirror what the fuzzing run did so signatures stay comparable
+                if config.debugger
+                    && triage.frames.is_empty()
+                    && let Some(report) = debugger::backtrace(config, &path)?
+                {
+                    triage.frames = report.frames;
+                }
+                Some(triage::signature(
+                    &category,
+                    triage.severity,
+                    &triage.frames,
+                    triage.panic.as_ref(),
+                ))
+            }
+            None => None,
+        };
+        let old_signature = crash.signature();
+
+        let result = RegressionResult {
+            report: report_dir.display().to_string(),
+            file: path.display().to_string(),
+            old_signature: old_signature.clone(),
+            new_signature: new_signature.clone(),
+        };
+
+        match new_signature {
+            None => summary.fixed.push(result),
+            Some(sig) if sig == old_signature => summary.still_crashing.push(result),
+            Some(_) => summary.changed.push(result),
+        }
+    }
+
+    Ok(())
+}
+
+// crash paths are recorded relative to where the fuzzer was started, fall back
+// to looking inside the report directory if the report has been moved
+pub fn locate_crash_file(report_dir: &Path, crash: &Crash) -> PathBuf {
+    let recorded = PathBuf::from(&crash.file);
+    if recorded.exists() {
+        return recorded;
+    }
+
+    match recorded.file_name() {
+        Some(name) => report_dir.join(&crash.category).join(name),
+        None => recorded,
+    }
+}
+
+fn replay(
+    config: &Config,
+    fuzz_type: &FuzzType,
+    path: &Path,
+    bytes: &[u8],
+) -> Result<TargetOutput> {
+    match fuzz_type {
+        FuzzType::String => run_target_string(config, bytes),
+        _ => {
+            // run_target_file expects the input to live in the mutations dir
+            let file_name = path.file_name().map_or_else(
+                || "regress.bin".to_string(),
+                |n| n.to_string_lossy().into_owned(),
+            );
+            fs::write(
+                config.temp_dir.path().join("mutations").join(&file_name),
+                bytes,
+            )?;
+            run_target_file(config, &file_name)
+        }
+    }
+}
+
+fn print_summary(summary: &RegressionSummary, json_path: &str) -> Result<()> {
+    let mut s = String::new();
+
+    writeln!(&mut s, "\n=====REGRESSION RESULTS=====")?;
+    writeln!(&mut s, "summary written to \n  `{json_path}`")?;
+    writeln!(&mut s, "still crashing: {}", summary.still_crashing.len())?;
+    writeln!(&mut s, "changed:        {}", summary.changed.len())?;
+    writeln!(&mut s, "fixed:          {}", summary.fixed.len())?;
+
+    for result in &summary.still_crashing {
+        writeln!(
+            &mut s,
+            "  [CRASHING] {} ({})",
+            result.file, result.old_signature
+        )?;
+    }
+    for result in &summary.changed {
+        writeln!(
+            &mut s,
+            "  [CHANGED]  {} ({} -> {})",
+            result.file,
+            result.old_signature,
+            result.new_signature.as_deref().unwrap_or_default()
+        )?;
+    }
+    for result in &summary.fixed {
+        writeln!(
+            &mut s,
+            "  [FIXED]    {} ({})",
+            result.file, result.old_signature
+        )?;
+    }
+    write!(&mut s, "============================")?;
+
+    info!("{s}");
+
+    Ok(())
+}
@@ -0,0 +1,260 @@
+use anyhow::{Result, anyhow};
+use log::{info, warn};
+use std::{
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+use crate::{
+    analysis::{Crash, Seed},
+    config::ReplayArgs,
+    corpus::{Corpus, CorpusEntry, recorded_partner},
+    dictionary::{DICTIONARY_FILE, Dictionary},
+    external::ExternalMutator,
+    formats::{
+        external::External, grammar::GrammarInput, jpeg::Jpeg, png::Png, string::FuzzString,
+        template::FileFormat, txt::Txt,
+    },
+    grammar::{GRAMMAR_FILE, Grammar},
+    mutate::MutationRecord,
+    mutations::{MutationContext, cmplog, deterministic, external},
+    regress::{RecordedConfig, RecordedCrashes, locate_crash_file},
+    types::FuzzType,
+};
+
+enum ReplayOutcome {
+    Matched,
+    Mismatched(usize), // offset of the first differing byte
+    Skipped(String),
+}
+
+pub fn run_replay(args: &ReplayArgs) -> Result<()> {
+    let report_dir = Path::new(&args.report);
+    let recorded_crashes: RecordedCrashes =
+        serde_json::from_str(&fs::read_to_string(report_dir.join("crashes.json"))?)?;
+    let recorded_config: RecordedConfig =
+        serde_json::from_str(&fs::read_to_string(report_dir.join("config.json"))?)?;
+
+    let crashes: Vec<&Crash> = recorded_crashes
+        .crashes
+        .iter()
+        .filter(|crash| args.iteration.is_none_or(|i| crash.iteration == i))
+        .collect();
+    if let Some(iteration) = args.iteration
+        && crashes.is_empty()
+    {
+        return Err(anyhow!("no crash was recorded at iteration {iteration}"));
+    }
+
+    // external mutations are re-applied by starting the same mutators again
+    let external = recorded_config
+        .external_mutators
+        .iter()
+        .map(|command| Ok(Rc::new(ExternalMutator::new(command)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut s = String::new();
+    let mut matched = 0;
+    writeln!(&mut s, "\n=====REPLAY RESULTS=====")?;
+    for crash in &crashes {
+        writeln!(&mut s, "{} (iteration {})", crash.file, crash.iteration)?;
+        let outcome = match recorded_config.validated_fuzz_type {
+            FuzzType::External => replay_crash::<External>(report_dir, crash, &external, &mut s)?,
+            FuzzType::Grammar => {
+                replay_crash::<GrammarInput>(report_dir, crash, &external, &mut s)?
+            }
+            FuzzType::Jpeg => replay_crash::<Jpeg>(report_dir, crash, &external, &mut s)?,
+            FuzzType::Png => replay_crash::<Png>(report_dir, crash, &external, &mut s)?,
+            FuzzType::String => replay_crash::<FuzzString>(report_dir, crash, &external, &mut s)?,
+            FuzzType::Txt => replay_crash::<Txt>(report_dir, crash, &external, &mut s)?,
+            _ => ReplayOutcome::Skipped("unsupported fuzz type".into()),
+        };
+
+        match outcome {
+            ReplayOutcome::Matched => {
+                matched += 1;
+                writeln!(&mut s, "  [MATCH]    regenerated input is identical")?;
+            }
+            ReplayOutcome::Mismatched(offset) => writeln!(
+                &mut s,
+                "  [MISMATCH] regenerated input differs from byte {offset}"
+            )?,
+            ReplayOutcome::Skipped(reason) => writeln!(&mut s, "  [SKIPPED]  {reason}")?,
+        }
+    }
+    writeln!(&mut s, "{matched}/{} crashes regenerated", crashes.len())?;
+    write!(&mut s, "========================")?;
+
+    info!("{s}");
+
+    Ok(())
+}
+
+/// re-applies the recorded mutations to the seed and compares the result
+/// with the saved crash input
+fn replay_crash<F: FileFormat>(
+    report_dir: &Path,
+    crash: &Crash,
+    external: &[Rc<ExternalMutator>],
+    s: &mut String,
+) -> Result<ReplayOutcome> {
+    let Some(seed) = &crash.seed else {
+        return Ok(ReplayOutcome::Skipped("no seed was recorded".into()));
+    };
+    let seed_path = locate_seed_file(report_dir, seed);
+    let seed_bytes = match fs::read(&seed_path) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            return Ok(ReplayOutcome::Skipped(format!(
+                "can't read seed {}: {e}",
+                seed_path.display()
+            )));
+        }
+    };
+    if format!("{:08x}", crc32fast::hash(&seed_bytes)) != seed.crc32 {
+        warn!("Seed {} doesn't match its recorded checksum", seed.name);
+    }
+    writeln!(s, "  seed `{}` ({} bytes)", seed.name, seed_bytes.len())?;
+
+    let regenerated = match crash.mutations.as_slice() {
+        // deterministic and cmplog inputs are made from the raw seed bytes
+        mutations
+            if !mutations.is_empty()
+                && mutations.iter().all(|recorded| {
+                    recorded.mutator.starts_with("deterministic::")
+                        || recorded.mutator.starts_with("cmplog::")
+                }) =>
+        {
+            replay_staged(&seed_bytes, mutations, s)?
+        }
+        mutations => replay_mutations::<F>(report_dir, &seed_bytes, mutations, external, s)?,
+    };
+
+    let saved = fs::read(locate_crash_file(report_dir, crash))?;
+    let first_difference = regenerated
+        .iter()
+        .zip(&saved)
+        .position(|(a, b)| a != b)
+        .or_else(|| (regenerated.len() != saved.len()).then(|| regenerated.len().min(saved.len())));
+
+    Ok(match first_difference {
+        Some(offset) => ReplayOutcome::Mismatched(offset),
+        None => ReplayOutcome::Matched,
+    })
+}
+
+fn replay_staged(
+    seed_bytes: &[u8],
+    mutations: &[MutationRecord],
+    s: &mut String,
+) -> Result<Vec<u8>> {
+    let mut bytes = seed_bytes.to_vec();
+    for (step, recorded) in mutations.iter().enumerate() {
+        writeln!(s, "  {}. {recorded}", step + 1)?;
+        match deterministic::replay(&bytes, recorded).or_else(|| cmplog::replay(&bytes, recorded)) {
+            Some(replayed) => bytes = replayed,
+            None => writeln!(s, "     re-applying this mutation had a different result")?,
+        }
+    }
+    Ok(bytes)
+}
+
+// every mutator is available, even ones the campaign had disabled
+fn replay_mutations<F: FileFormat>(
+    report_dir: &Path,
+    seed_bytes: &[u8],
+    mutations: &[MutationRecord],
+    external: &[Rc<ExternalMutator>],
+    s: &mut String,
+) -> Result<Vec<u8>> {
+    // token mutations pick from the dictionary saved with the report
+    let dictionary_path = report_dir.join(DICTIONARY_FILE);
+    let dictionary = if dictionary_path.is_file() {
+        Dictionary::load(&dictionary_path.to_string_lossy())?
+    } else {
+        Dictionary::default()
+    };
+
+    // grammar mutations parse the seed with the grammar saved with the report
+    let grammar_path = report_dir.join(GRAMMAR_FILE);
+    let grammar = if grammar_path.is_file() {
+        Some(Rc::new(Grammar::load(&grammar_path.to_string_lossy())?))
+    } else {
+        None
+    };
+
+    let context = MutationContext {
+        dictionary,
+        corpus: splice_partners(report_dir, F::EXT, mutations),
+        external: external.to_vec(),
+        grammar,
+    };
+    let mutators = external::mutators::<F>(&Rc::new(context));
+    let mut model = F::parse(seed_bytes)?;
+    for (step, recorded) in mutations.iter().enumerate() {
+        let applied = match mutators.get(&recorded.mutator) {
+            Some(mutator) => mutator.apply(&mut model, recorded.rng_seed)?,
+            None => None,
+        };
+        writeln!(s, "  {}. {recorded}", step + 1)?;
+
+        let diverged = applied.is_none_or(|applied| {
+            applied.mutator != recorded.mutator
+                || applied.target != recorded.target
+                || applied.offset != recorded.offset
+        });
+        if diverged {
+            writeln!(s, "     re-applying this mutation had a different result")?;
+        }
+    }
+    F::generate(model)
+}
+
+// the corpus entries recorded splices used, read back from the report's
+// seeds folder. a partner that wasn't saved makes its splice diverge
+fn splice_partners(report_dir: &Path, extension: &str, mutations: &[MutationRecord]) -> Corpus {
+    let extension = if extension.is_empty() {
+        "bin"
+    } else {
+        extension
+    };
+
+    let mut corpus_len = 0;
+    let mut known = Vec::new();
+    for (partner, record) in mutations
+        .iter()
+        .filter_map(|record| Some((recorded_partner(record)?, record)))
+    {
+        corpus_len = partner.corpus_len;
+        let path = report_dir
+            .join("seeds")
+            .join(format!("seed-{}.{extension}", partner.crc32));
+        match fs::read(&path) {
+            Ok(content) => {
+                let name = record
+                    .params
+                    .get("partner_name")
+                    .and_then(|name| name.as_str())
+                    .unwrap_or_default();
+                known.push((partner.index, CorpusEntry::new(name.to_string(), content)));
+            }
+            Err(e) => warn!("Can't read splice partner {}: {e}", path.display()),
+        }
+    }
+    Corpus::partial(corpus_len, known)
+}
+
+// like crash files, seed paths are relative to where the fuzzer was started
+fn locate_seed_file(report_dir: &Path, seed: &Seed) -> PathBuf {
+    let recorded = PathBuf::from(&seed.file);
+    if recorded.exists() {
+        return recorded;
+    }
+
+    match recorded.file_name() {
+        Some(name) => report_dir.join("seeds").join(name),
+        None => recorded,
+    }
+}
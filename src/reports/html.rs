@@ -0,0 +1,298 @@
+use std::{collections::BTreeMap, fmt::Write, fs};
+
+use anyhow::Result;
+
+use crate::{
+    analysis::{Crash, CrashAnalyzer},
+    triage::Severity,
+    types::Config,
+    utils::{escape_markup as escape, reproduce_command},
+};
+
+// how much of each crash input is shown in the hexdump preview
+const HEXDUMP_PREVIEW_LEN: usize = 256;
+const TIMELINE_WIDTH: u128 = 800;
+const TIMELINE_ROW_HEIGHT: usize = 20;
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;color:#222}\
+table{border-collapse:collapse;margin-bottom:1em}\
+td,th{border:1px solid #ccc;padding:4px 8px;text-align:left;vertical-align:top}\
+th{background:#eee}pre{background:#f6f6f6;padding:8px;overflow-x:auto;font-size:12px}\
+details{margin:4px 0}summary{cursor:pointer}h2{border-bottom:1px solid #ccc}";
+
+// Renders a self-contained html report of the run. Everything, including the
+// styling and the timeline, is inlined so the file can be attached to a ticket.
+pub fn render(analyzer: &CrashAnalyzer, config: &Config) -> Result<String> {
+    let mut s = String::new();
+
+    writeln!(&mut s, "<!DOCTYPE html>")?;
+    writeln!(&mut s, "<html><head><meta charset=\"utf-8\">")?;
+    writeln!(
+        &mut s,
+        "<title>anomie report {}</title>",
+        escape(&config.report_path)
+    )?;
+    writeln!(&mut s, "<style>{STYLE}</style></head><body>")?;
+    writeln!(&mut s, "<h1>anomie report</h1>")?;
+
+    render_stats(&mut s, analyzer, config)?;
+    render_timeline(&mut s, analyzer)?;
+    render_buckets(&mut s, analyzer, config)?;
+    render_mutators(&mut s, analyzer)?;
+
+    writeln!(&mut s, "</body></html>")?;
+    Ok(s)
+}
+
+fn render_stats(s: &mut String, analyzer: &CrashAnalyzer, config: &Config) -> Result<()> {
+    let stats = &analyzer.stats;
+    writeln!(s, "<h2>Run</h2><table>")?;
+    let rows = [
+        ("binary", escape(&config.bin_path)),
+        ("arguments", escape(&config.bin_args.join(" "))),
+        ("fuzz type", format!("{:?}", config.validated_fuzz_type)),
+        ("iterations", config.iterations.to_string()),
+        ("timeout", format!("{} ms", config.timeout)),
+        ("duration", format!("{} ms", analyzer.duration_ms)),
+        ("total hits", stats.total.to_string()),
+        ("timeouts", stats.timeout.to_string()),
+        ("sanitizer", stats.sanitizer.to_string()),
+        ("panics", stats.panic.to_string()),
+        ("out of memory", stats.oom.to_string()),
+        ("unknown", stats.unknown.to_string()),
+    ];
+    for (name, value) in rows {
+        writeln!(s, "<tr><th>{name}</th><td>{value}</td></tr>")?;
+    }
+    for (signal, count) in &stats.signals {
+        writeln!(
+            s,
+            "<tr><th>{}</th><td>{count}</td></tr>",
+            signal.to_lowercase()
+        )?;
+    }
+    for (rule, count) in &stats.findings {
+        writeln!(s, "<tr><th>{}</th><td>{count}</td></tr>", escape(rule))?;
+    }
+    writeln!(s, "</table>")?;
+    Ok(())
+}
+
+// one row per severity, each crash is a dot placed by when it was found
+fn render_timeline(s: &mut String, analyzer: &CrashAnalyzer) -> Result<()> {
+    writeln!(s, "<h2>Timeline</h2>")?;
+    if analyzer.crashes.is_empty() {
+        writeln!(s, "<p>No crashes were found.</p>")?;
+        return Ok(());
+    }
+
+    let mut severities: Vec<Severity> = analyzer.crashes.iter().map(|c| c.severity).collect();
+    severities.dedup();
+
+    let label_width: u128 = 180;
+    let height = severities.len() * TIMELINE_ROW_HEIGHT + 10;
+    let duration = analyzer.duration_ms.max(1);
+    writeln!(
+        s,
+        "<svg width=\"{}\" height=\"{height}\" xmlns=\"http://www.w3.org/2000/svg\">",
+        TIMELINE_WIDTH + label_width + 10
+    )?;
+    for (row, severity) in severities.iter().enumerate() {
+        let y = row * TIMELINE_ROW_HEIGHT + 15;
+        writeln!(
+            s,
+            "<text x=\"0\" y=\"{}\" font-size=\"12\">{}</text>",
+            y + 4,
+            severity.label()
+        )?;
+        writeln!(
+            s,
+            "<line x1=\"{label_width}\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"#ccc\"/>",
+            TIMELINE_WIDTH + label_width
+        )?;
+        for crash in analyzer.crashes.iter().filter(|c| c.severity == *severity) {
+            let x = label_width + crash.elapsed_ms * TIMELINE_WIDTH / duration;
+            writeln!(
+                s,
+                "<circle cx=\"{x}\" cy=\"{y}\" r=\"4\" fill=\"{}\"><title>iteration {} at {} ms</title></circle>",
+                severity_color(*severity),
+                crash.iteration,
+                crash.elapsed_ms
+            )?;
+        }
+    }
+    writeln!(s, "</svg>")?;
+    writeln!(s, "<p>0 ms to {} ms</p>", analyzer.duration_ms)?;
+    Ok(())
+}
+
+fn render_mutators(s: &mut String, analyzer: &CrashAnalyzer) -> Result<()> {
+    if analyzer.mutators.is_empty() {
+        return Ok(());
+    }
+
+    writeln!(s, "<h2>Mutators</h2>")?;
+    writeln!(
+        s,
+        "<table><tr><th>mutator</th><th>executions</th><th>crashes</th><th>new behaviours</th><th>final probability</th></tr>"
+    )?;
+    for mutator in &analyzer.mutators {
+        writeln!(
+            s,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:.1}%</td></tr>",
+            escape(&mutator.name),
+            mutator.executions,
+            mutator.crashes,
+            mutator.new_behaviours,
+            mutator.probability * 100.0
+        )?;
+    }
+    writeln!(s, "</table>")?;
+    Ok(())
+}
+
+fn render_buckets(s: &mut String, analyzer: &CrashAnalyzer, config: &Config) -> Result<()> {
+    // crashes are sorted by severity, so the buckets keep that order
+    let mut buckets: BTreeMap<(Severity, String), Vec<&Crash>> = BTreeMap::new();
+    for crash in &analyzer.crashes {
+        buckets
+            .entry((crash.severity, crash.signature()))
+            .or_default()
+            .push(crash);
+    }
+
+    writeln!(s, "<h2>Crashes</h2>")?;
+    for ((severity, signature), crashes) in buckets {
+        writeln!(
+            s,
+            "<h3 style=\"color:{}\">{} ({})</h3>",
+            severity_color(severity),
+            escape(&signature),
+            crashes.len()
+        )?;
+        writeln!(
+            s,
+            "<table><tr><th>iteration</th><th>time</th><th>fault address</th><th>file</th><th>details</th></tr>"
+        )?;
+        for crash in crashes {
+            render_crash(s, crash, config)?;
+        }
+        writeln!(s, "</table>")?;
+    }
+    Ok(())
+}
+
+fn render_crash(s: &mut String, crash: &Crash, config: &Config) -> Result<()> {
+    let fault_address = crash
+        .fault_address
+        .map_or_else(String::new, |addr| format!("{addr:#x}"));
+    writeln!(
+        s,
+        "<tr><td>{}</td><td>{} ms</td><td>{fault_address}</td><td>{}</td><td>",
+        crash.iteration,
+        crash.elapsed_ms,
+        escape(&crash.file)
+    )?;
+
+    writeln!(
+        s,
+        "<details><summary>mutations ({})</summary><ol>",
+        crash.mutations.len()
+    )?;
+    for mutation in &crash.mutations {
+        writeln!(s, "<li>{}</li>", escape(&mutation.to_string()))?;
+    }
+    writeln!(s, "</ol></details>")?;
+
+    if let Some(derivation) = &crash.derivation {
+        writeln!(
+            s,
+            "<details><summary>derivation</summary><pre>{}</pre></details>",
+            escape(derivation)
+        )?;
+    }
+
+    let bytes = fs::read(&crash.file).unwrap_or_default();
+    writeln!(
+        s,
+        "<details><summary>input ({} bytes)</summary><pre>{}</pre></details>",
+        bytes.len(),
+        escape(&hexdump(&bytes[..bytes.len().min(HEXDUMP_PREVIEW_LEN)]))
+    )?;
+
+    if let Some(panic) = &crash.panic {
+        writeln!(
+            s,
+            "<pre>thread '{}' panicked at {}:\n{}</pre>",
+            escape(&panic.thread),
+            escape(&panic.location),
+            escape(&panic.message)
+        )?;
+    }
+
+    if !crash.frames.is_empty() {
+        writeln!(s, "<details><summary>backtrace</summary><pre>")?;
+        for (i, frame) in crash.frames.iter().enumerate() {
+            let location = match (&frame.file, frame.line) {
+                (Some(file), Some(line)) => format!(" at {file}:{line}"),
+                _ => String::new(),
+            };
+            writeln!(s, "#{i} {}{}", escape(&frame.function), escape(&location))?;
+        }
+        if let Some(instruction) = &crash.faulting_instruction {
+            writeln!(s, "\n=> {}", escape(instruction))?;
+        }
+        writeln!(s, "</pre></details>")?;
+    }
+
+    if !crash.stderr.is_empty() {
+        writeln!(
+            s,
+            "<details><summary>stderr</summary><pre>{}</pre></details>",
+            escape(&crash.stderr)
+        )?;
+    }
+
+    writeln!(
+        s,
+        "<details><summary>reproduce</summary><pre>{}</pre></details>",
+        escape(&reproduce_command(config, crash))
+    )?;
+    writeln!(s, "</td></tr>")?;
+    Ok(())
+}
+
+fn hexdump(bytes: &[u8]) -> String {
+    let mut out = String::new();
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x}  ", row * 16);
+        for i in 0..16 {
+            match chunk.get(i) {
+                Some(b) => {
+                    let _ = write!(out, "{b:02x} ");
+                }
+                None => out.push_str("   "),
+            }
+        }
+        out.push(' ');
+        for &b in chunk {
+            out.push(if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            });
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn severity_color(severity: Severity) -> &'static str {
+    match severity {
+        Severity::WriteAccessViolation => "#b00020",
+        Severity::AccessViolation | Severity::ReadAccessViolation => "#d9480f",
+        Severity::StackExhaustion | Severity::ReadNearNull => "#e67700",
+        Severity::AbortAssertion | Severity::Arithmetic => "#1864ab",
+        Severity::ResourceExhaustion | Severity::Hang | Severity::Unknown => "#495057",
+    }
+}
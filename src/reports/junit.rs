@@ -0,0 +1,119 @@
+use std::{collections::BTreeMap, fmt::Write};
+
+use anyhow::Result;
+
+use crate::{
+    analysis::{Crash, CrashAnalyzer},
+    triage::Severity,
+    types::Config,
+    utils::{escape_markup as escape, reproduce_command},
+};
+
+// Renders the run as a JUnit XML test suite. The campaign itself is a passing
+// test case and every unique crash bucket is a failing one, so CI systems show
+// new findings as test failures.
+pub fn render(analyzer: &CrashAnalyzer, config: &Config) -> Result<String> {
+    let mut buckets: BTreeMap<(Severity, String), Vec<&Crash>> = BTreeMap::new();
+    for crash in &analyzer.crashes {
+        buckets
+            .entry((crash.severity, crash.signature()))
+            .or_default()
+            .push(crash);
+    }
+
+    let suite_name = format!("anomie.{:?}", config.validated_fuzz_type).to_lowercase();
+    let mut s = String::new();
+
+    writeln!(&mut s, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>")?;
+    writeln!(
+        &mut s,
+        "<testsuites name=\"anomie\" tests=\"{}\" failures=\"{}\" time=\"{}\">",
+        buckets.len() + 1,
+        buckets.len(),
+        seconds(analyzer.duration_ms)
+    )?;
+    writeln!(
+        &mut s,
+        "  <testsuite name=\"{suite_name}\" tests=\"{}\" failures=\"{}\" errors=\"0\" skipped=\"0\" time=\"{}\" timestamp=\"{}\">",
+        buckets.len() + 1,
+        buckets.len(),
+        seconds(analyzer.duration_ms),
+        analyzer.started_at.trim_end_matches('Z')
+    )?;
+
+    writeln!(&mut s, "    <properties>")?;
+    writeln!(
+        &mut s,
+        "      <property name=\"binary\" value=\"{}\"/>",
+        escape(&config.bin_path)
+    )?;
+    writeln!(
+        &mut s,
+        "      <property name=\"iterations\" value=\"{}\"/>",
+        config.iterations
+    )?;
+    writeln!(
+        &mut s,
+        "      <property name=\"report\" value=\"{}\"/>",
+        escape(&config.report_path)
+    )?;
+    writeln!(&mut s, "    </properties>")?;
+
+    writeln!(
+        &mut s,
+        "    <testcase classname=\"{suite_name}\" name=\"campaign\" time=\"{}\">",
+        seconds(analyzer.duration_ms)
+    )?;
+    writeln!(
+        &mut s,
+        "      <system-out>{} iterations, {} total hits</system-out>",
+        config.iterations, analyzer.stats.total
+    )?;
+    writeln!(&mut s, "    </testcase>")?;
+
+    for ((severity, signature), crashes) in &buckets {
+        let first = crashes[0];
+        writeln!(
+            &mut s,
+            "    <testcase classname=\"{suite_name}\" name=\"{}\" time=\"{}\">",
+            escape(signature),
+            seconds(first.elapsed_ms)
+        )?;
+        writeln!(
+            &mut s,
+            "      <failure type=\"{severity:?}\" message=\"{} {}, first found at iteration {}\">",
+            crashes.len(),
+            if crashes.len() == 1 {
+                "crash"
+            } else {
+                "crashes"
+            },
+            first.iteration
+        )?;
+        for crash in crashes {
+            writeln!(&mut s, "{}", escape(&crash.file))?;
+        }
+        writeln!(
+            &mut s,
+            "\nreproduce with:\n{}",
+            escape(&reproduce_command(config, first))
+        )?;
+        writeln!(&mut s, "      </failure>")?;
+        if !first.stderr.is_empty() {
+            writeln!(
+                &mut s,
+                "      <system-err>{}</system-err>",
+                escape(&first.stderr)
+            )?;
+        }
+        writeln!(&mut s, "    </testcase>")?;
+    }
+
+    writeln!(&mut s, "  </testsuite>")?;
+    writeln!(&mut s, "</testsuites>")?;
+    Ok(s)
+}
+
+fn seconds(ms: u128) -> String {
+    format!("{:.3}", ms as f64 / 1000.0)
+}
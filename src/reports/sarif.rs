@@ -0,0 +1,189 @@
+use std::{env, fmt::Write, fs, path::Path};
+
+use serde_json::{Value, json};
+
+use crate::{
+    analysis::{Crash, CrashAnalyzer},
+    triage::{Severity, crashing_frame},
+    types::Config,
+};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+// relative source paths are resolved against the checkout by the consumer
+const SRCROOT: &str = "%SRCROOT%";
+
+// Builds a SARIF 2.1 log of the run. Each crash category (the report folder
+// it was saved in) becomes a rule, and every crash becomes a result pointing at
+// the crashing frame when it is symbolized. The crashing input is attached
+// as an artifact so the finding can be reproduced from the dashboard.
+pub fn render(analyzer: &CrashAnalyzer, config: &Config) -> Value {
+    let mut rule_ids: Vec<&str> = analyzer
+        .crashes
+        .iter()
+        .map(|c| c.category.as_str())
+        .collect();
+    rule_ids.sort_unstable();
+    rule_ids.dedup();
+
+    let rules: Vec<Value> = rule_ids
+        .iter()
+        .map(|id| {
+            json!({
+                "id": id,
+                "name": id,
+                "shortDescription": { "text": format!("target crashed with {id}") },
+            })
+        })
+        .collect();
+
+    let artifacts: Vec<Value> = analyzer
+        .crashes
+        .iter()
+        .map(|crash| {
+            json!({
+                "location": input_location(&crash.file),
+                "length": fs::metadata(&crash.file).map_or(-1, |m| i64::try_from(m.len()).unwrap_or(-1)),
+                "roles": ["attachment"],
+            })
+        })
+        .collect();
+
+    let results: Vec<Value> = analyzer
+        .crashes
+        .iter()
+        .enumerate()
+        .map(|(artifact_index, crash)| {
+            let rule_index = rule_ids
+                .iter()
+                .position(|id| *id == crash.category)
+                .unwrap_or_default();
+            render_result(crash, rule_index, artifact_index)
+        })
+        .collect();
+
+    json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": rules,
+                }
+            },
+            "invocations": [{
+                "executionSuccessful": true,
+                "commandLine": format!("{} {}", config.bin_path, config.bin_args.join(" ")),
+            }],
+            "artifacts": artifacts,
+            "results": results,
+        }]
+    })
+}
+
+fn render_result(crash: &Crash, rule_index: usize, artifact_index: usize) -> Value {
+    let input_location = json!({
+        "physicalLocation": {
+            "artifactLocation": artifact_location(&crash.file, artifact_index),
+        },
+        "message": { "text": "crashing input" },
+    });
+
+    // the frame the triage signature uses when it has a source file,
+    // otherwise point at the input
+    let frame = crashing_frame(&crash.frames);
+    let locations = match frame.and_then(|f| Some((f, f.file.as_deref()?))) {
+        Some((frame, file)) => {
+            let mut region = json!({ "startLine": frame.line.unwrap_or(1) });
+            if let Some(column) = frame.column {
+                region["startColumn"] = json!(column);
+            }
+            json!([{
+                "physicalLocation": {
+                    "artifactLocation": source_location(file),
+                    "region": region,
+                },
+                "logicalLocations": [{ "fullyQualifiedName": frame.function, "kind": "function" }],
+            }])
+        }
+        None => json!([input_location.clone()]),
+    };
+
+    let mut message = format!(
+        "{} ({}) found at iteration {}",
+        crash.category,
+        crash.severity.label(),
+        crash.iteration
+    );
+    if let Some(addr) = crash.fault_address {
+        message.push_str(&format!(", faulting address {addr:#x}"));
+    }
+
+    json!({
+        "ruleId": crash.category,
+        "ruleIndex": rule_index,
+        "level": level(crash.severity),
+        "message": { "text": message },
+        "locations": locations,
+        "relatedLocations": [input_location],
+        "partialFingerprints": { "anomieSignature/v1": crash.signature() },
+        "properties": {
+            "severity": crash.severity,
+            "mutations": crash.mutations,
+        },
+    })
+}
+
+// crash inputs are local files, they get absolute file:// uris
+fn input_location(file: &str) -> Value {
+    let path = fs::canonicalize(file)
+        .or_else(|_| env::current_dir().map(|dir| dir.join(file)))
+        .unwrap_or_else(|_| file.into());
+    json!({ "uri": file_uri(&path) })
+}
+
+fn artifact_location(file: &str, index: usize) -> Value {
+    let mut location = input_location(file);
+    location["index"] = json!(index);
+    location
+}
+
+// debug info paths are either absolute or relative to the source root
+fn source_location(file: &str) -> Value {
+    if Path::new(file).is_absolute() {
+        json!({ "uri": file_uri(Path::new(file)) })
+    } else {
+        json!({ "uri": percent_encode(file), "uriBaseId": SRCROOT })
+    }
+}
+
+fn file_uri(path: &Path) -> String {
+    format!("file://{}", percent_encode(&path.to_string_lossy()))
+}
+
+// everything but unreserved characters and path separators is escaped
+fn percent_encode(path: &str) -> String {
+    let mut uri = String::new();
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~/".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            let _ = write!(&mut uri, "%{byte:02X}");
+        }
+    }
+    uri
+}
+
+fn level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::WriteAccessViolation
+        | Severity::AccessViolation
+        | Severity::ReadAccessViolation => "error",
+        Severity::StackExhaustion
+        | Severity::ReadNearNull
+        | Severity::AbortAssertion
+        | Severity::Arithmetic => "warning",
+        Severity::ResourceExhaustion | Severity::Hang | Severity::Unknown => "note",
+    }
+}
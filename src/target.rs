@@ -1,72 +1,213 @@
-use anyhow::{Result, bail};
+use anyhow::{Result, anyhow, bail};
 use log::debug;
 use std::{
-    io::Read,
-    os::unix::process::ExitStatusExt,
+    env,
+    ffi::CString,
+    fs,
+    io::{self, Read},
+    mem,
+    os::unix::{ffi::OsStrExt, process::CommandExt},
+    path::Path,
     process::{Child, Command, Stdio},
-    time::Duration,
+    thread,
+    time::{Duration, Instant},
 };
-use wait_timeout::ChildExt;
 
 use crate::{errors::ExitStatus, types::Config};
 
-fn run_child(child: &mut Child, timeout: Duration) -> Result<ExitStatus> {
-    if let Some(status) = child.wait_timeout(timeout)? {
-        let mut stdout = String::new();
-        child
-            .stdout
-            .as_mut()
-            .unwrap()
-            .read_to_string(&mut stdout)
-            .unwrap();
-        let mut stderr = String::new();
-        child
-            .stderr
-            .as_mut()
-            .unwrap()
-            .read_to_string(&mut stderr)
-            .unwrap();
-
-        debug!(
-            "Code: {:?} (SIG {:?})\nSTDOUT returned: {:?}\nSTDERR returned: {:?}",
-            status.code(),
-            status.signal().unwrap_or(0),
-            stdout,
-            stderr,
-        );
-
-        if let Some(sig) = status.signal() {
-            Ok(ExitStatus::Signal(sig))
-        } else if let Some(code) = status.code() {
-            Ok(ExitStatus::ExitCode(code))
-        } else {
-            Ok(ExitStatus::Error("Unknown termination".into()))
+// how often a running target is checked on
+const WAIT_POLL_INTERVAL: Duration = Duration::from_micros(500);
+
+// Everything we keep from a single execution of the target
+pub struct TargetOutput {
+    pub status: ExitStatus,
+    pub stderr: String,
+    pub duration_ms: u128,
+    pub peak_rss_kb: Option<u64>,
+    pub oom_killed: bool, // the kernel's oom killer ended the target
+}
+
+impl TargetOutput {
+    pub fn from_status(status: ExitStatus) -> Self {
+        Self {
+            status,
+            stderr: String::new(),
+            duration_ms: 0,
+            peak_rss_kb: None,
+            oom_killed: false,
+        }
+    }
+}
+
+// Waits for the child to exit or the timeout to pass. wait4 is polled instead
+// of using wait_timeout so the kernel also reports the child's peak memory.
+fn run_child(child: &mut Child, timeout: Duration) -> Result<TargetOutput> {
+    let start = Instant::now();
+    let pid = child.id() as libc::pid_t;
+    let mut wait_status = 0;
+    // SAFETY: rusage is plain old data, all zeroes is a valid value
+    let mut rusage: libc::rusage = unsafe { mem::zeroed() };
+
+    loop {
+        // SAFETY: pid is our own unreaped child and both out pointers are valid
+        let ret = unsafe { libc::wait4(pid, &raw mut wait_status, libc::WNOHANG, &raw mut rusage) };
+        if ret == pid {
+            break;
+        }
+        if ret < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            return Err(err.into());
         }
+
+        if start.elapsed() >= timeout {
+            child.kill()?;
+            child.wait()?;
+            return Ok(TargetOutput {
+                duration_ms: start.elapsed().as_millis(),
+                ..TargetOutput::from_status(ExitStatus::Timeout(timeout.as_millis()))
+            });
+        }
+        thread::sleep(WAIT_POLL_INTERVAL);
+    }
+    let duration_ms = start.elapsed().as_millis();
+
+    let mut stdout = String::new();
+    child
+        .stdout
+        .as_mut()
+        .unwrap()
+        .read_to_string(&mut stdout)
+        .unwrap();
+    let mut stderr = String::new();
+    child
+        .stderr
+        .as_mut()
+        .unwrap()
+        .read_to_string(&mut stderr)
+        .unwrap();
+
+    let status = if libc::WIFSIGNALED(wait_status) {
+        ExitStatus::Signal(libc::WTERMSIG(wait_status))
+    } else if libc::WIFEXITED(wait_status) {
+        ExitStatus::ExitCode(libc::WEXITSTATUS(wait_status))
     } else {
-        child.kill()?;
-        child.wait()?;
-        Ok(ExitStatus::Timeout(timeout.as_millis()))
+        ExitStatus::Error("Unknown termination".into())
+    };
+
+    debug!("Status: {status:?}\nSTDOUT returned: {stdout:?}\nSTDERR returned: {stderr:?}");
+
+    Ok(TargetOutput {
+        status,
+        stderr,
+        duration_ms,
+        // linux reports ru_maxrss in kilobytes
+        peak_rss_kb: u64::try_from(rusage.ru_maxrss).ok(),
+        oom_killed: false,
+    })
+}
+
+// Starts the target with the configured resource limits and waits for it. If
+// a cgroup is configured the target joins it before it execs, so everything it
+// allocates is accounted, and the cgroup's oom_kill counter tells us whether
+// the kernel killed it.
+pub fn spawn_target(config: &Config, mut command: Command) -> Result<TargetOutput> {
+    command.stdout(Stdio::piped()).stderr(Stdio::piped());
+
+    if let Some(limit_mb) = config.memory_limit {
+        let limit_bytes = limit_mb.saturating_mul(1024 * 1024);
+        // SAFETY: setrlimit is async-signal-safe and only touches the child's limits
+        unsafe {
+            command.pre_exec(move || {
+                let limit = libc::rlimit {
+                    rlim_cur: limit_bytes,
+                    rlim_max: limit_bytes,
+                };
+                if libc::setrlimit(libc::RLIMIT_AS, &raw const limit) != 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    if let Some(cgroup) = &config.cgroup {
+        let procs = CString::new(
+            Path::new(cgroup)
+                .join("cgroup.procs")
+                .as_os_str()
+                .as_bytes(),
+        )?;
+        // SAFETY: open, write and close are async-signal-safe and the path is
+        // allocated before the fork
+        unsafe {
+            command.pre_exec(move || {
+                let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                // "0" moves the writing process, the child itself
+                let written = libc::write(fd, b"0".as_ptr().cast(), 1);
+                let err = io::Error::last_os_error();
+                libc::close(fd);
+                if written != 1 {
+                    return Err(err);
+                }
+                Ok(())
+            });
+        }
+    }
+
+    let oom_kills_before = config.cgroup.as_deref().map(oom_kill_count).transpose()?;
+    // a child that fails to join the cgroup never runs the target, spawn
+    // reaps it and returns the error
+    let mut child = command.spawn().map_err(|e| match &config.cgroup {
+        Some(cgroup) => anyhow!("can't start the target in cgroup {cgroup}: {e}"),
+        None => e.into(),
+    })?;
+
+    let mut output = run_child(&mut child, Duration::from_millis(config.timeout))?;
+    if let (Some(cgroup), Some(before)) = (&config.cgroup, oom_kills_before) {
+        output.oom_killed = oom_kill_count(cgroup)? > before;
     }
+    Ok(output)
 }
 
-pub fn run_target_file(config: &Config, mutated_file_name: &str) -> Result<ExitStatus> {
-    let mut binary_args = config.bin_args.clone();
+// reads the oom_kill counter of a cgroup v2 memory controller
+fn oom_kill_count(cgroup: &str) -> Result<u64> {
+    let events = fs::read_to_string(Path::new(cgroup).join("memory.events"))?;
+    Ok(events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|count| count.trim().parse().ok())
+        .unwrap_or(0))
+}
 
-    let mutated_file_path = config
-        .temp_dir
-        .path()
-        .join("mutations")
-        .join(mutated_file_name);
+// Command for the target with the environment every run shares. Rust targets
+// print a backtrace on panic so they can be bucketed like sanitizer reports.
+pub fn target_command(program: &str) -> Command {
+    let mut command = Command::new(program);
+    if env::var_os("RUST_BACKTRACE").is_none() {
+        command.env("RUST_BACKTRACE", "1");
+    }
+    command
+}
+
+// substitutes the placeholders in the binary args for a file input
+pub fn file_args(config: &Config, input_path: &Path) -> Result<Vec<String>> {
+    let mut binary_args = config.bin_args.clone();
 
     let mut replaced = false;
     for arg in &mut binary_args {
         if arg == "{input}" {
-            *arg = mutated_file_path.to_string_lossy().into_owned();
+            *arg = input_path.to_string_lossy().into_owned();
             replaced = true;
-        } else if arg.contains("{temp_dir}") {
-            if let Some(path) = config.temp_dir.path().to_str() {
-                *arg = arg.replace("{temp_dir}", path);
-            }
+        } else if arg.contains("{temp_dir}")
+            && let Some(path) = config.temp_dir.path().to_str()
+        {
+            *arg = arg.replace("{temp_dir}", path);
         }
     }
 
@@ -74,25 +215,37 @@ pub fn run_target_file(config: &Config, mutated_file_name: &str) -> Result<ExitS
         bail!("Binary args missing {{input}} placeholder");
     }
 
-    debug!("Running: {:?} {:?}", config.bin_path, binary_args.join(" "));
-
-    let timeout = Duration::from_millis(config.timeout);
-    let mut child = Command::new(&config.bin_path)
-        .args(binary_args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-    let exit_status = run_child(&mut child, timeout)?;
-    Ok(exit_status)
+    Ok(binary_args)
 }
 
-pub fn run_target_string(config: &Config, fuzz_input: &[u8]) -> Result<ExitStatus> {
+// appends a fuzzed string input to the binary args
+pub fn string_args(config: &Config, fuzz_input: &[u8]) -> Vec<String> {
     let mut input_args = config.bin_args.clone();
     let fuzz_string_delim: &[String] = &fuzz_input
         .split(|&b| b == b' ') // use a space to delimit the args
         .map(|s| String::from_utf8_lossy(s).into_owned())
         .collect::<Vec<String>>();
     input_args.extend_from_slice(fuzz_string_delim);
+    input_args
+}
+
+pub fn run_target_file(config: &Config, mutated_file_name: &str) -> Result<TargetOutput> {
+    let mutated_file_path = config
+        .temp_dir
+        .path()
+        .join("mutations")
+        .join(mutated_file_name);
+    let binary_args = file_args(config, &mutated_file_path)?;
+
+    debug!("Running: {:?} {:?}", config.bin_path, binary_args.join(" "));
+
+    let mut command = target_command(&config.bin_path);
+    command.args(binary_args);
+    spawn_target(config, command)
+}
+
+pub fn run_target_string(config: &Config, fuzz_input: &[u8]) -> Result<TargetOutput> {
+    let input_args = string_args(config, fuzz_input);
 
     let coalesced_args = input_args.join(" ");
     // Print the full command being executed for debug
@@ -103,13 +256,7 @@ pub fn run_target_string(config: &Config, fuzz_input: &[u8]) -> Result<ExitStatu
         debug!("Running: {coalesced_args}");
     }
 
-    let timeout = Duration::from_millis(config.timeout);
-    let mut child = Command::new(&config.bin_path)
-        .args(input_args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()?;
-
-    let exit_status = run_child(&mut child, timeout)?;
-    Ok(exit_status)
+    let mut command = target_command(&config.bin_path);
+    command.args(input_args);
+    spawn_target(config, command)
 }
@@ -0,0 +1,329 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::ExitStatus, target::TargetOutput};
+
+// anything faulting below this address is treated as a null pointer dereference
+const NULL_PAGE_LIMIT: u64 = 0x10000;
+
+static FAULT_ADDRESS: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:on|at) (?:unknown )?address (?:0x)?([0-9a-fA-F]+)").unwrap());
+static STACK_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^\s*#(\d+) 0x[0-9a-fA-F]+ in (\S+)(?: ([^\s:()]+):(\d+)(?::(\d+))?)?").unwrap()
+});
+// a sanitizer stack trace is rarely useful past the first few frames
+const MAX_FRAMES: usize = 8;
+
+// the rust runtime exits with this code when the main thread panics
+pub const RUST_PANIC_EXIT_CODE: i32 = 101;
+
+// rust >= 1.73 puts the message on the line after the location, older
+// versions quote it before the location
+static PANIC: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"thread '([^']*)'(?: \(\d+\))? panicked at ([^\s:]+:\d+:\d+):\r?\n(.*)").unwrap()
+});
+static LEGACY_PANIC: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"thread '([^']*)'(?: \(\d+\))? panicked at '(.*)', ([^\s:]+:\d+:\d+)").unwrap()
+});
+static RUST_FRAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^ +\d+: ([^\r\n]+)(?:\r?\n +at ([^\s:]+):(\d+)(?::(\d+))?)?").unwrap()
+});
+static DIGITS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\d+").unwrap());
+
+static ACCESS_KIND: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^(READ|WRITE) of size \d+|caused by a (READ|WRITE) memory access").unwrap()
+});
+
+// Severity buckets used to prioritize findings. The variants are ordered from
+// most to least severe so sorting a list of crashes puts the interesting
+// ones first.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    WriteAccessViolation, // out of bounds writes, use after free writes, double frees
+    AccessViolation,      // bad memory access where the direction is unknown
+    ReadAccessViolation,  // out of bounds reads away from the null page
+    StackExhaustion,      // stack overflow, usually unbounded recursion
+    ReadNearNull,         // null pointer dereference
+    AbortAssertion,       // abort(), failed assertions and traps
+    Arithmetic,           // division by zero and friends
+    ResourceExhaustion,   // ran out of memory
+    Hang,                 // exceeded the timeout
+    Unknown,
+}
+
+impl Severity {
+    pub fn label(self) -> &'static str {
+        match self {
+            Severity::WriteAccessViolation => "write access violation",
+            Severity::AccessViolation => "access violation",
+            Severity::ReadAccessViolation => "read access violation",
+            Severity::StackExhaustion => "stack exhaustion",
+            Severity::ReadNearNull => "read near null",
+            Severity::AbortAssertion => "abort/assertion",
+            Severity::Arithmetic => "arithmetic error",
+            Severity::ResourceExhaustion => "resource exhaustion",
+            Severity::Hang => "hang",
+            Severity::Unknown => "unknown",
+        }
+    }
+}
+
+// A single frame of a symbolized stack trace, file and line are only known
+// when the target was built with debug info
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    pub function: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+// A panic reported by a rust target
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct RustPanic {
+    pub thread: String,
+    pub message: String,
+    pub location: String, // file:line:column
+}
+
+// Result of triaging a single finding
+pub struct Triage {
+    pub severity: Severity,
+    pub fault_address: Option<u64>,
+    pub frames: Vec<StackFrame>,
+    pub panic: Option<RustPanic>,
+}
+
+// crashes sharing a signature are treated as the same underlying bug. panics
+// are bucketed by where they happened and what they said, otherwise the
+// crashing function is used when a backtrace is available.
+pub fn signature(
+    category: &str,
+    severity: Severity,
+    frames: &[StackFrame],
+    panic: Option<&RustPanic>,
+) -> String {
+    if let Some(panic) = panic {
+        // numbers in panic messages are usually indices or lengths that vary per input
+        let message = DIGITS.replace_all(&panic.message, "N");
+        return format!("{category}/{}/{message}", panic.location);
+    }
+
+    match crashing_frame(frames) {
+        Some(frame) => format!("{category}/{severity:?}/{}", frame.function),
+        None => format!("{category}/{severity:?}"),
+    }
+}
+
+// the first frame that belongs to the target rather than libc, the rust
+// standard library or a sanitizer runtime
+pub fn crashing_frame(frames: &[StackFrame]) -> Option<&StackFrame> {
+    const RUNTIME_FUNCTIONS: [&str; 6] = [
+        "raise",
+        "abort",
+        "gsignal",
+        "pthread_kill",
+        "kill",
+        "rust_begin_unwind",
+    ];
+    const RUNTIME_PREFIXES: [&str; 7] = [
+        "__", "std::", "core::", "alloc::", "<std::", "<core::", "<alloc::",
+    ];
+    frames.iter().find(|frame| {
+        !RUNTIME_PREFIXES
+            .iter()
+            .any(|prefix| frame.function.starts_with(prefix))
+            && !RUNTIME_FUNCTIONS.contains(&frame.function.as_str())
+            && !frame.file.as_deref().is_some_and(|f| {
+                f.contains("sanitizer_common")
+                    || f.contains("compiler-rt")
+                    || f.starts_with("/rustc/")
+            })
+    })
+}
+
+// what allocators, sanitizers and language runtimes print when an allocation
+// fails, eg. under an address space limit
+const OUT_OF_MEMORY_PATTERNS: [&str; 6] = [
+    "ERROR: AddressSanitizer: out of memory",
+    "ERROR: AddressSanitizer: requested allocation size",
+    "ERROR: libFuzzer: out-of-memory",
+    "std::bad_alloc",
+    "Cannot allocate memory",
+    "memory allocation of", // rust: "memory allocation of N bytes failed"
+];
+
+// returns true if the target ran out of memory, either the kernel killed it or
+// an allocation failed and the target said so. the patterns are common in
+// regular error messages too, so they only count when the run ended abnormally
+pub fn is_out_of_memory(output: &TargetOutput) -> bool {
+    let abnormal = match &output.status {
+        ExitStatus::Signal(_) => true,
+        ExitStatus::ExitCode(code) => *code != 0 || has_sanitizer_report(&output.stderr),
+        ExitStatus::Timeout(_) | ExitStatus::Error(_) => false,
+    };
+    output.oom_killed
+        || (abnormal
+            && OUT_OF_MEMORY_PATTERNS
+                .iter()
+                .any(|pattern| output.stderr.contains(pattern)))
+}
+
+// returns true if stderr contains a report from one of the sanitizers
+pub fn has_sanitizer_report(stderr: &str) -> bool {
+    stderr.contains("ERROR: AddressSanitizer")
+        || stderr.contains("ERROR: MemorySanitizer")
+        || stderr.contains("ERROR: LeakSanitizer")
+        || stderr.contains("WARNING: ThreadSanitizer")
+        || stderr.contains("runtime error:")
+}
+
+// classify a finding from its exit status and whatever the target wrote to stderr.
+// sanitizer reports take priority over the raw signal since they tell us the
+// access type and faulting address.
+pub fn classify(output: &TargetOutput) -> Triage {
+    let (status, stderr) = (&output.status, output.stderr.as_str());
+    let fault_address = FAULT_ADDRESS
+        .captures(stderr)
+        .and_then(|c| u64::from_str_radix(&c[1], 16).ok());
+
+    let frames = parse_frames(stderr);
+    let panic = parse_panic(stderr);
+
+    // an out of memory report is more specific than the abort that follows it
+    if is_out_of_memory(output) {
+        return Triage {
+            severity: Severity::ResourceExhaustion,
+            fault_address,
+            frames,
+            panic,
+        };
+    }
+
+    if let Some(severity) = classify_sanitizer(stderr, fault_address) {
+        return Triage {
+            severity,
+            fault_address,
+            frames,
+            panic,
+        };
+    }
+
+    // a panic unwinds (exit code 101) or aborts, either way it's a failed assertion
+    if panic.is_some() {
+        return Triage {
+            severity: Severity::AbortAssertion,
+            fault_address,
+            frames,
+            panic,
+        };
+    }
+
+    let severity = match status {
+        ExitStatus::Signal(libc::SIGSEGV) => match fault_address {
+            Some(addr) if addr < NULL_PAGE_LIMIT => Severity::ReadNearNull,
+            _ => Severity::AccessViolation,
+        },
+        ExitStatus::Signal(libc::SIGBUS) => Severity::AccessViolation,
+        ExitStatus::Signal(libc::SIGABRT | libc::SIGILL | libc::SIGTRAP | libc::SIGSYS) => {
+            Severity::AbortAssertion
+        }
+        ExitStatus::Signal(libc::SIGFPE) => Severity::Arithmetic,
+        ExitStatus::Timeout(_) => Severity::Hang,
+        _ => Severity::Unknown,
+    };
+
+    Triage {
+        severity,
+        fault_address,
+        frames,
+        panic,
+    }
+}
+
+// returns the first panic in stderr, if the target panicked
+pub fn parse_panic(stderr: &str) -> Option<RustPanic> {
+    if let Some(c) = PANIC.captures(stderr) {
+        return Some(RustPanic {
+            thread: c[1].to_string(),
+            message: c[3].trim().to_string(),
+            location: c[2].to_string(),
+        });
+    }
+
+    LEGACY_PANIC.captures(stderr).map(|c| RustPanic {
+        thread: c[1].to_string(),
+        message: c[2].to_string(),
+        location: c[3].to_string(),
+    })
+}
+
+// pulls the frames of the first stack trace out of a sanitizer report, or out
+// of a rust panic backtrace if there is no sanitizer report
+pub fn parse_frames(stderr: &str) -> Vec<StackFrame> {
+    let frames = parse_sanitizer_frames(stderr);
+    if !frames.is_empty() || !stderr.contains("stack backtrace:") {
+        return frames;
+    }
+
+    RUST_FRAME
+        .captures_iter(stderr)
+        .map(|c| StackFrame {
+            function: c[1].to_string(),
+            file: c.get(2).map(|m| m.as_str().to_string()),
+            line: c.get(3).and_then(|m| m.as_str().parse().ok()),
+            column: c.get(4).and_then(|m| m.as_str().parse().ok()),
+        })
+        .filter(|frame| crashing_frame(std::slice::from_ref(frame)).is_some())
+        .take(MAX_FRAMES)
+        .collect()
+}
+
+fn parse_sanitizer_frames(stderr: &str) -> Vec<StackFrame> {
+    let mut frames = Vec::new();
+    for c in STACK_FRAME.captures_iter(stderr) {
+        // the allocation/free traces that follow also start counting from #0
+        if (&c[1] == "0" && !frames.is_empty()) || frames.len() == MAX_FRAMES {
+            break;
+        }
+        frames.push(StackFrame {
+            function: c[2].to_string(),
+            file: c.get(3).map(|m| m.as_str().to_string()),
+            line: c.get(4).and_then(|m| m.as_str().parse().ok()),
+            column: c.get(5).and_then(|m| m.as_str().parse().ok()),
+        });
+    }
+    frames
+}
+
+fn classify_sanitizer(stderr: &str, fault_address: Option<u64>) -> Option<Severity> {
+    if !has_sanitizer_report(stderr) {
+        if stderr.contains("Assertion") && stderr.contains("failed") {
+            return Some(Severity::AbortAssertion);
+        }
+        return None;
+    }
+
+    if stderr.contains("stack-overflow") {
+        return Some(Severity::StackExhaustion);
+    }
+    if stderr.contains("double-free") || stderr.contains("attempting free on address") {
+        return Some(Severity::WriteAccessViolation);
+    }
+
+    let write = ACCESS_KIND
+        .captures(stderr)
+        .and_then(|c| c.get(1).or_else(|| c.get(2)))
+        .map(|m| m.as_str() == "WRITE");
+
+    match (write, fault_address) {
+        (Some(true), _) => Some(Severity::WriteAccessViolation),
+        (Some(false), Some(addr)) if addr < NULL_PAGE_LIMIT => Some(Severity::ReadNearNull),
+        (Some(false), _) => Some(Severity::ReadAccessViolation),
+        (None, Some(addr)) if addr < NULL_PAGE_LIMIT => Some(Severity::ReadNearNull),
+        (None, Some(_)) => Some(Severity::AccessViolation),
+        (None, None) => None,
+    }
+}
@@ -1,9 +1,14 @@
-use std::path::PathBuf;
+use std::{collections::BTreeMap, path::PathBuf, rc::Rc};
 
 use rand::rngs::SmallRng;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use tempfile::TempDir;
 
+use crate::{
+    dictionary::Dictionary, external::ExternalMutator, grammar::Grammar,
+    mutations::MutatorSelection, oracle::OracleRule,
+};
+
 // Fuzzer global config, this is the struct used after input validation
 // (eg. verifying the binary exists, making sure the FuzzType is valid)
 // at the end of a run, the config is serialized to a json which is
@@ -12,9 +17,32 @@ use tempfile::TempDir;
 pub struct Config {
     pub bin_args: Vec<String>,
     pub bin_path: String,
+    pub debugger: bool,
+    pub cmplog: Option<String>, // comparison tracing build of the target
+    pub deterministic_max_len: usize, // in bytes, 0 disables the stage
+    pub dictionaries: Vec<String>,
+    pub auto_dict: bool,
     pub iterations: usize,
+    pub min_mutations: usize, // stacked on each random input
+    pub max_mutations: usize,
+    pub energy: usize,             // random inputs made from a seed in a row
+    pub memory_limit: Option<u64>, // in MB
+    pub mutators: MutatorSelection,
+    pub external_mutators: Vec<String>, // commands of the external mutator processes
+    pub grammar_path: Option<String>,
+    pub adaptive_schedule: bool,
+    pub cgroup: Option<String>,
+    pub oracles: Vec<OracleRule>,
     pub report_path: String,
 
+    #[serde(skip)]
+    pub dictionary: Dictionary, // tokens from the dictionaries
+    #[serde(skip)]
+    pub auto_dictionary: Dictionary, // tokens extracted from the target
+    #[serde(skip)]
+    pub external: Vec<Rc<ExternalMutator>>,
+    #[serde(skip)]
+    pub grammar: Option<Rc<Grammar>>,
     #[serde(skip)]
     pub rng: SmallRng, // skip this when serializing
     #[serde(skip)]
@@ -29,20 +57,22 @@ pub struct Config {
 #[derive(Serialize, Debug)]
 pub struct CrashStats {
     pub total: u64,
-    pub sigill: u64,
-    pub sigabrt: u64,
-    pub sigfpe: u64,
-    pub sigsegv: u64,
-    pub sigpipe: u64,
-    pub sigterm: u64,
+    pub signals: BTreeMap<String, u64>, // keyed by signal name, eg. SIGSEGV
     pub timeout: u64,
+    pub oom: u64,
+    pub unknown: u64,
+    pub sanitizer: u64,
+    pub panic: u64,
+    pub findings: BTreeMap<String, u64>, // keyed by oracle rule name
 }
 
 // Describes the types supported by the fuzzer
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FuzzType {
     String,
     Txt,
+    External,    // structure only known to an external mutator
+    Grammar,     // text described by a user supplied grammar
     Jpeg,        // TODO
     Png,         // TODO
     Pdf,         // TODO
@@ -4,18 +4,32 @@ use std::{
     fmt::Write,
     fs::{self, DirEntry, create_dir},
     path::Path,
+    time::{SystemTime, UNIX_EPOCH},
 };
 
-use crate::{analysis::CrashAnalyzer, types::Config};
+use crate::{
+    analysis::{Crash, CrashAnalyzer},
+    reports::{html, junit, sarif},
+    triage::Severity,
+    types::{Config, FuzzType},
+};
 
-pub fn initialize(config: &mut Config) -> Result<()> {
-    // create the temporary directories that will be dropped when the fuzzer finishes
+// where {temp_dir} points when a crash is reproduced by hand
+const REPRODUCE_TEMP_DIR: &str = "/tmp/anomie";
+
+// create the temporary directories that will be dropped when the fuzzer finishes
+pub fn create_temp_dirs(config: &Config) -> Result<()> {
     let corpus_dir = config.temp_dir.path().join("corpus/");
     let mutations_dir = config.temp_dir.path().join("mutations/");
     let scratch_dir = config.temp_dir.path().join("scratch/");
     create_dir(corpus_dir)?;
     create_dir(mutations_dir)?;
     create_dir(scratch_dir)?;
+    Ok(())
+}
+
+pub fn initialize(config: &mut Config) -> Result<()> {
+    create_temp_dirs(config)?;
 
     // create the report dir
     if !Path::new(&config.report_path).exists() {
@@ -27,14 +41,12 @@ pub fn initialize(config: &mut Config) -> Result<()> {
     let mut max_index = 0;
     for entry in fs::read_dir(&config.report_path)? {
         let entry = entry?;
-        if entry.file_type()?.is_dir() {
-            if let Some(name) = entry.file_name().to_str() {
-                if let Ok(num) = name.parse::<u32>() {
-                    if num > max_index {
-                        max_index = num;
-                    }
-                }
-            }
+        if entry.file_type()?.is_dir()
+            && let Some(name) = entry.file_name().to_str()
+            && let Ok(num) = name.parse::<u32>()
+            && num > max_index
+        {
+            max_index = num;
         }
     }
 
@@ -43,14 +55,8 @@ pub fn initialize(config: &mut Config) -> Result<()> {
     let new_dir_path = config.report_path.clone() + "/" + &dir_num;
     debug!("Creating subdir at {new_dir_path}");
     fs::create_dir(new_dir_path.clone())?;
-    fs::create_dir(new_dir_path.clone() + "/SIGILL")?;
-    fs::create_dir(new_dir_path.clone() + "/SIGABRT")?;
-    fs::create_dir(new_dir_path.clone() + "/SIGFPE")?;
-    fs::create_dir(new_dir_path.clone() + "/SIGSEGV")?;
-    fs::create_dir(new_dir_path.clone() + "/SIGPIPE")?;
-    fs::create_dir(new_dir_path.clone() + "/SIGTERM")?;
-    fs::create_dir(new_dir_path.clone() + "/TIMEOUT")?;
-    fs::create_dir(new_dir_path.clone() + "/UNKNOWN")?;
+    // crash folders are created as crashes are recorded
+    fs::create_dir(new_dir_path.clone() + "/seeds")?;
 
     // update the subdir num in config
     info!(
@@ -86,9 +92,109 @@ pub fn create_run_json(analyzer: &CrashAnalyzer, config: &Config) -> Result<()>
 
     let crashes_json = serde_json::to_string(&analyzer)?;
     fs::write(config.report_path.clone() + "/crashes.json", crashes_json)?;
+
+    let crashes_sarif = serde_json::to_string(&sarif::render(analyzer, config))?;
+    fs::write(config.report_path.clone() + "/crashes.sarif", crashes_sarif)?;
+
+    let junit_xml = junit::render(analyzer, config)?;
+    fs::write(config.report_path.clone() + "/junit.xml", junit_xml)?;
+
+    let report_html = html::render(analyzer, config)?;
+    fs::write(config.report_path.clone() + "/report.html", report_html)?;
     Ok(())
 }
 
+// builds a shell command that runs the target against a recorded crash
+pub fn reproduce_command(config: &Config, crash: &Crash) -> String {
+    let mut args = config.bin_args.clone();
+    if let FuzzType::String = config.validated_fuzz_type {
+        let bytes = fs::read(&crash.file).unwrap_or_default();
+        args.extend(
+            bytes
+                .split(|&b| b == b' ')
+                .map(|s| String::from_utf8_lossy(s).into_owned()),
+        );
+    }
+
+    let mut needs_temp_dir = false;
+    let mut command = shell_quote(&config.bin_path);
+    for arg in args {
+        let arg = if arg == "{input}" {
+            crash.file.clone()
+        } else if arg.contains("{temp_dir}") {
+            needs_temp_dir = true;
+            arg.replace("{temp_dir}", REPRODUCE_TEMP_DIR)
+        } else {
+            arg
+        };
+        command.push(' ');
+        command.push_str(&shell_quote(&arg));
+    }
+
+    if needs_temp_dir {
+        format!("mkdir -p {REPRODUCE_TEMP_DIR}/scratch && {command}")
+    } else {
+        command
+    }
+}
+
+// escapes text for use inside html/xml elements and attributes. characters
+// xml 1.0 doesn't allow at all (control characters other than tab and newlines,
+// eg. the escapes of ansi colours) become U+FFFD
+pub fn escape_markup(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            '\u{0}'..='\u{1F}' | '\u{FFFE}' | '\u{FFFF}' => out.push('\u{FFFD}'),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+// formats a point in time as an ISO 8601 UTC timestamp, eg. 2025-01-31T13:05:09Z
+pub fn iso8601(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let days = secs / 86_400;
+    let rem = secs % 86_400;
+
+    // civil date from days since the epoch, Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+fn shell_quote(arg: &str) -> String {
+    if !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./=:,+".contains(c))
+    {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
 pub fn print_report(analyzer: &CrashAnalyzer, config: &Config) -> Result<()> {
     let crash_stats = &analyzer.stats;
     let mut s = String::new();
@@ -101,14 +207,56 @@ pub fn print_report(analyzer: &CrashAnalyzer, config: &Config) -> Result<()> {
         config.report_path
     )?;
     writeln!(&mut s, "total hits:   {}", crash_stats.total)?;
-    writeln!(&mut s, "sigill hits:  {}", crash_stats.sigill)?;
-    writeln!(&mut s, "sigabrt hits: {}", crash_stats.sigabrt)?;
-    writeln!(&mut s, "sigfpe hits:  {}", crash_stats.sigfpe)?;
-    writeln!(&mut s, "sigsegv hits: {}", crash_stats.sigsegv)?;
-    writeln!(&mut s, "sigpipe hits: {}", crash_stats.sigpipe)?;
-    writeln!(&mut s, "sigterm hits: {}", crash_stats.sigterm)?;
+    for (signal, count) in &crash_stats.signals {
+        writeln!(&mut s, "{} hits: {count}", signal.to_lowercase())?;
+    }
     writeln!(&mut s, "timeouts:     {}", crash_stats.timeout)?;
-    writeln!(&mut s, "unknown hits: {}", crash_stats.timeout)?;
+    writeln!(&mut s, "sanitizer:    {}", crash_stats.sanitizer)?;
+    writeln!(&mut s, "panics:       {}", crash_stats.panic)?;
+    writeln!(&mut s, "out of memory: {}", crash_stats.oom)?;
+    for (rule, count) in &crash_stats.findings {
+        writeln!(&mut s, "{rule} hits: {count}")?;
+    }
+    writeln!(&mut s, "unknown hits: {}", crash_stats.unknown)?;
+
+    // crashes are already sorted, so this lists the most severe buckets first
+    if !analyzer.crashes.is_empty() {
+        writeln!(&mut s, "-----BY SEVERITY-----")?;
+        let mut current: Option<Severity> = None;
+        for crash in &analyzer.crashes {
+            if current != Some(crash.severity) {
+                let count = analyzer
+                    .crashes
+                    .iter()
+                    .filter(|c| c.severity == crash.severity)
+                    .count();
+                writeln!(&mut s, "{} ({count}):", crash.severity.label())?;
+                current = Some(crash.severity);
+            }
+            writeln!(&mut s, "  [{}] {}", crash.category, crash.file)?;
+        }
+    }
+
+    // mutators that never ran are left out
+    if analyzer.mutators.iter().any(|m| m.executions > 0) {
+        writeln!(&mut s, "-----MUTATORS-----")?;
+        writeln!(
+            &mut s,
+            "{:<32} {:>10} {:>8} {:>8} {:>7}",
+            "mutator", "executions", "crashes", "new", "weight"
+        )?;
+        for mutator in analyzer.mutators.iter().filter(|m| m.executions > 0) {
+            writeln!(
+                &mut s,
+                "{:<32} {:>10} {:>8} {:>8} {:>6.1}%",
+                mutator.name,
+                mutator.executions,
+                mutator.crashes,
+                mutator.new_behaviours,
+                mutator.probability * 100.0
+            )?;
+        }
+    }
     write!(&mut s, "==========================")?;
 
     info!("{s}");
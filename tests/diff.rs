@@ -0,0 +1,59 @@
+use std::{fs, path::Path, process::Command};
+
+fn write_report(dir: &Path, crashes: &str, iterations: u64, signal: &str) {
+    fs::create_dir_all(dir).unwrap();
+    fs::write(
+        dir.join("crashes.json"),
+        format!(r#"{{"crashes":[{crashes}],"stats":{{"total":1,"signals":{{"{signal}":1}}}}}}"#),
+    )
+    .unwrap();
+    fs::write(
+        dir.join("config.json"),
+        format!(r#"{{"iterations":{iterations},"validated_fuzz_type":"Txt"}}"#),
+    )
+    .unwrap();
+}
+
+#[test]
+fn diff_reports_new_and_disappeared_signatures() {
+    let temp = tempfile::tempdir().unwrap();
+    let old = temp.path().join("0001");
+    let new = temp.path().join("0002");
+    let json = temp.path().join("diff.json");
+
+    write_report(
+        &old,
+        r#"{"file":"a","category":"SIGSEGV","severity":"AccessViolation","fault_address":null,"mutations":[]}"#,
+        10,
+        "SIGSEGV",
+    );
+    write_report(
+        &new,
+        r#"{"file":"b","category":"SIGABRT","severity":"AbortAssertion","fault_address":null,"mutations":[]}"#,
+        20,
+        "SIGABRT",
+    );
+
+    let status = Command::new(env!("CARGO_BIN_EXE_anomie"))
+        .arg("diff")
+        .arg(&old)
+        .arg(&new)
+        .arg("--json")
+        .arg(&json)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let diff: serde_json::Value = serde_json::from_str(&fs::read_to_string(json).unwrap()).unwrap();
+    assert_eq!(diff["new"][0]["signature"], "SIGABRT/AbortAssertion");
+    assert_eq!(
+        diff["disappeared"][0]["signature"],
+        "SIGSEGV/AccessViolation"
+    );
+    assert_eq!(diff["common"].as_array().unwrap().len(), 0);
+    assert_eq!(diff["config"][0]["key"], "iterations");
+    assert_eq!(diff["stats"][0]["key"], "signals.SIGABRT");
+    assert_eq!(diff["stats"][1]["key"], "signals.SIGSEGV");
+    assert_eq!(diff["stats"][1]["old"], 1);
+    assert_eq!(diff["stats"][1]["new"], serde_json::Value::Null);
+}